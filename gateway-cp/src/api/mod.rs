@@ -1,7 +1,7 @@
 use axum::{
     http::StatusCode,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{get, post, put},
     Json, Router,
 };
 use sqlx::SqlitePool;
@@ -35,6 +35,10 @@ pub fn router(state: AppState) -> Router {
                 .put(routes::update_route)
                 .delete(routes::delete_route),
         )
+        .route(
+            "/routes/:id/split",
+            put(routes::put_route_split).delete(routes::delete_route_split),
+        )
         .with_state(state)
 }
 
@@ -5,7 +5,11 @@ use axum::{
     Json,
 };
 
-use crate::{db, model::RouteSpec, service};
+use crate::{
+    db,
+    model::{RouteSpec, TrafficSplit},
+    service,
+};
 use tracing::info;
 
 use super::{map_db_error, ApiError, AppState};
@@ -14,6 +18,7 @@ pub async fn create_route(
     State(state): State<AppState>,
     Json(route): Json<RouteSpec>,
 ) -> Result<impl IntoResponse, ApiError> {
+    service::validate_route_spec(&route).map_err(|err| ApiError::validation(err.details))?;
     service::validate_route_policies(&state.pool, &route)
         .await
         .map_err(|err| ApiError::validation(err.details))?;
@@ -56,6 +61,7 @@ pub async fn update_route(
         route.id = id;
     }
 
+    service::validate_route_spec(&route).map_err(|err| ApiError::validation(err.details))?;
     service::validate_route_policies(&state.pool, &route)
         .await
         .map_err(|err| ApiError::validation(err.details))?;
@@ -95,3 +101,51 @@ pub async fn delete_route(
     info!(route_id = %id, "route deleted");
     Ok(StatusCode::NO_CONTENT)
 }
+
+/// Replaces only the route's traffic split, so a canary can be promoted or
+/// rolled back without resending the whole route.
+pub async fn put_route_split(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(split): Json<TrafficSplit>,
+) -> Result<impl IntoResponse, ApiError> {
+    let route = set_route_split(&state, &id, Some(split)).await?;
+    info!(route_id = %id, "route split updated");
+    Ok(Json(route))
+}
+
+pub async fn delete_route_split(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    set_route_split(&state, &id, None).await?;
+    info!(route_id = %id, "route split removed");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn set_route_split(
+    state: &AppState,
+    id: &str,
+    split: Option<TrafficSplit>,
+) -> Result<RouteSpec, ApiError> {
+    let mut route = db::get_route(&state.pool, id)
+        .await
+        .map_err(map_db_error)?
+        .ok_or_else(|| ApiError::not_found("route not found"))?;
+    route.split = split;
+    service::validate_route_spec(&route).map_err(|err| ApiError::validation(err.details))?;
+
+    let rows = db::update_route(&state.pool, &route)
+        .await
+        .map_err(map_db_error)?;
+    if rows == 0 {
+        return Err(ApiError::not_found("route not found"));
+    }
+
+    state
+        .config_state
+        .publish_from_db(&state.pool)
+        .await
+        .map_err(map_db_error)?;
+    Ok(route)
+}
@@ -8,6 +8,18 @@ pub struct GatewayCpConfig {
     pub grpc_bind: String,
     pub logging: LoggingConfig,
     pub database_url: String,
+    #[serde(default)]
+    pub grpc_tls: Option<GrpcTlsConfig>,
+}
+
+/// TLS for the config gRPC endpoint. When `client_ca_path` is set, data
+/// planes must present a certificate signed by that CA (mutual TLS).
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct GrpcTlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    pub client_ca_path: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -18,6 +30,7 @@ pub struct LoggingConfig {
 }
 
 impl GatewayCpConfig {
+    #[allow(clippy::result_large_err)]
     pub fn load(path: &str) -> Result<Self, figment::Error> {
         Figment::new()
             .merge(Toml::file(path))
@@ -43,6 +43,16 @@ pub async fn init(pool: &SqlitePool) -> Result<(), sqlx::Error> {
             upstreams_json TEXT NOT NULL,
             lb TEXT,
             failover_json TEXT,
+            rewrite_json TEXT,
+            request_headers_json TEXT,
+            response_headers_json TEXT,
+            limits_json TEXT,
+            timeouts_json TEXT,
+            rate_limit_json TEXT,
+            hash_key_json TEXT,
+            sticky_session_json TEXT,
+            mirror_json TEXT,
+            split_json TEXT,
             policies_json TEXT NOT NULL,
             created_at INTEGER NOT NULL,
             updated_at INTEGER NOT NULL
@@ -52,6 +62,31 @@ pub async fn init(pool: &SqlitePool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    migrate_routes_table(pool).await?;
+
+    Ok(())
+}
+
+async fn migrate_routes_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    for column in [
+        "rewrite_json",
+        "request_headers_json",
+        "response_headers_json",
+        "limits_json",
+        "timeouts_json",
+        "rate_limit_json",
+        "hash_key_json",
+        "sticky_session_json",
+        "mirror_json",
+        "split_json",
+    ] {
+        if !column_exists(pool, "routes", column).await? {
+            sqlx::query(&format!("ALTER TABLE routes ADD COLUMN {column} TEXT"))
+                .execute(pool)
+                .await?;
+        }
+    }
+
     Ok(())
 }
 
@@ -9,13 +9,29 @@ pub async fn insert_route(pool: &SqlitePool, route: &RouteSpec) -> Result<(), sq
         serde_json::to_string(&route.upstreams).unwrap_or_else(|_| "[]".to_string());
     let failover_json =
         serde_json::to_string(&route.failover).unwrap_or_else(|_| "null".to_string());
+    let rewrite_json = serde_json::to_string(&route.rewrite).unwrap_or_else(|_| "null".to_string());
+    let request_headers_json =
+        serde_json::to_string(&route.request_headers).unwrap_or_else(|_| "null".to_string());
+    let response_headers_json =
+        serde_json::to_string(&route.response_headers).unwrap_or_else(|_| "null".to_string());
+    let limits_json = serde_json::to_string(&route.limits).unwrap_or_else(|_| "null".to_string());
+    let timeouts_json =
+        serde_json::to_string(&route.timeouts).unwrap_or_else(|_| "null".to_string());
+    let rate_limit_json =
+        serde_json::to_string(&route.rate_limit).unwrap_or_else(|_| "null".to_string());
+    let hash_key_json =
+        serde_json::to_string(&route.hash_key).unwrap_or_else(|_| "null".to_string());
+    let sticky_session_json =
+        serde_json::to_string(&route.sticky_session).unwrap_or_else(|_| "null".to_string());
+    let mirror_json = serde_json::to_string(&route.mirror).unwrap_or_else(|_| "null".to_string());
+    let split_json = serde_json::to_string(&route.split).unwrap_or_else(|_| "null".to_string());
     let policies_json = serde_json::to_string(&route.policies).unwrap_or_else(|_| "[]".to_string());
     let now = current_ts();
 
     sqlx::query(
         r#"
-        INSERT INTO routes (id, match_json, upstreams_json, lb, failover_json, policies_json, created_at, updated_at)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        INSERT INTO routes (id, match_json, upstreams_json, lb, failover_json, rewrite_json, request_headers_json, response_headers_json, limits_json, timeouts_json, rate_limit_json, hash_key_json, sticky_session_json, mirror_json, split_json, policies_json, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
         "#,
     )
     .bind(&route.id)
@@ -23,6 +39,16 @@ pub async fn insert_route(pool: &SqlitePool, route: &RouteSpec) -> Result<(), sq
     .bind(upstreams_json)
     .bind(&route.lb)
     .bind(failover_json)
+    .bind(rewrite_json)
+    .bind(request_headers_json)
+    .bind(response_headers_json)
+    .bind(limits_json)
+    .bind(timeouts_json)
+    .bind(rate_limit_json)
+    .bind(hash_key_json)
+    .bind(sticky_session_json)
+    .bind(mirror_json)
+    .bind(split_json)
     .bind(policies_json)
     .bind(now)
     .bind(now)
@@ -35,7 +61,10 @@ pub async fn insert_route(pool: &SqlitePool, route: &RouteSpec) -> Result<(), sq
 pub async fn list_routes(pool: &SqlitePool) -> Result<Vec<RouteSpec>, sqlx::Error> {
     let rows = sqlx::query(
         r#"
-        SELECT id, match_json, upstreams_json, lb, failover_json, policies_json
+        SELECT id, match_json, upstreams_json, lb, failover_json, rewrite_json,
+               request_headers_json, response_headers_json, limits_json,
+               timeouts_json, rate_limit_json, hash_key_json, sticky_session_json,
+               mirror_json, split_json, policies_json
         FROM routes
         ORDER BY id ASC
         "#,
@@ -49,7 +78,10 @@ pub async fn list_routes(pool: &SqlitePool) -> Result<Vec<RouteSpec>, sqlx::Erro
 pub async fn get_route(pool: &SqlitePool, id: &str) -> Result<Option<RouteSpec>, sqlx::Error> {
     let row = sqlx::query(
         r#"
-        SELECT id, match_json, upstreams_json, lb, failover_json, policies_json
+        SELECT id, match_json, upstreams_json, lb, failover_json, rewrite_json,
+               request_headers_json, response_headers_json, limits_json,
+               timeouts_json, rate_limit_json, hash_key_json, sticky_session_json,
+               mirror_json, split_json, policies_json
         FROM routes
         WHERE id = ?1
         "#,
@@ -67,6 +99,22 @@ pub async fn update_route(pool: &SqlitePool, route: &RouteSpec) -> Result<u64, s
         serde_json::to_string(&route.upstreams).unwrap_or_else(|_| "[]".to_string());
     let failover_json =
         serde_json::to_string(&route.failover).unwrap_or_else(|_| "null".to_string());
+    let rewrite_json = serde_json::to_string(&route.rewrite).unwrap_or_else(|_| "null".to_string());
+    let request_headers_json =
+        serde_json::to_string(&route.request_headers).unwrap_or_else(|_| "null".to_string());
+    let response_headers_json =
+        serde_json::to_string(&route.response_headers).unwrap_or_else(|_| "null".to_string());
+    let limits_json = serde_json::to_string(&route.limits).unwrap_or_else(|_| "null".to_string());
+    let timeouts_json =
+        serde_json::to_string(&route.timeouts).unwrap_or_else(|_| "null".to_string());
+    let rate_limit_json =
+        serde_json::to_string(&route.rate_limit).unwrap_or_else(|_| "null".to_string());
+    let hash_key_json =
+        serde_json::to_string(&route.hash_key).unwrap_or_else(|_| "null".to_string());
+    let sticky_session_json =
+        serde_json::to_string(&route.sticky_session).unwrap_or_else(|_| "null".to_string());
+    let mirror_json = serde_json::to_string(&route.mirror).unwrap_or_else(|_| "null".to_string());
+    let split_json = serde_json::to_string(&route.split).unwrap_or_else(|_| "null".to_string());
     let policies_json = serde_json::to_string(&route.policies).unwrap_or_else(|_| "[]".to_string());
     let now = current_ts();
 
@@ -77,8 +125,18 @@ pub async fn update_route(pool: &SqlitePool, route: &RouteSpec) -> Result<u64, s
             upstreams_json = ?3,
             lb = ?4,
             failover_json = ?5,
-            policies_json = ?6,
-            updated_at = ?7
+            rewrite_json = ?6,
+            request_headers_json = ?7,
+            response_headers_json = ?8,
+            limits_json = ?9,
+            timeouts_json = ?10,
+            rate_limit_json = ?11,
+            hash_key_json = ?12,
+            sticky_session_json = ?13,
+            mirror_json = ?14,
+            split_json = ?15,
+            policies_json = ?16,
+            updated_at = ?17
         WHERE id = ?1
         "#,
     )
@@ -87,6 +145,16 @@ pub async fn update_route(pool: &SqlitePool, route: &RouteSpec) -> Result<u64, s
     .bind(upstreams_json)
     .bind(&route.lb)
     .bind(failover_json)
+    .bind(rewrite_json)
+    .bind(request_headers_json)
+    .bind(response_headers_json)
+    .bind(limits_json)
+    .bind(timeouts_json)
+    .bind(rate_limit_json)
+    .bind(hash_key_json)
+    .bind(sticky_session_json)
+    .bind(mirror_json)
+    .bind(split_json)
     .bind(policies_json)
     .bind(now)
     .execute(pool)
@@ -112,12 +180,52 @@ fn row_to_route(row: sqlx::sqlite::SqliteRow) -> Result<RouteSpec, sqlx::Error>
     let upstreams_json: String = row.try_get("upstreams_json")?;
     let lb: Option<String> = row.try_get("lb")?;
     let failover_json: String = row.try_get("failover_json")?;
+    let rewrite_json: Option<String> = row.try_get("rewrite_json")?;
+    let request_headers_json: Option<String> = row.try_get("request_headers_json")?;
+    let response_headers_json: Option<String> = row.try_get("response_headers_json")?;
+    let limits_json: Option<String> = row.try_get("limits_json")?;
+    let timeouts_json: Option<String> = row.try_get("timeouts_json")?;
+    let rate_limit_json: Option<String> = row.try_get("rate_limit_json")?;
+    let hash_key_json: Option<String> = row.try_get("hash_key_json")?;
+    let sticky_session_json: Option<String> = row.try_get("sticky_session_json")?;
+    let mirror_json: Option<String> = row.try_get("mirror_json")?;
+    let split_json: Option<String> = row.try_get("split_json")?;
     let policies_json: String = row.try_get("policies_json")?;
 
     let match_rules =
         serde_json::from_str(&match_json).unwrap_or(serde_json::Value::Object(Default::default()));
     let upstreams = serde_json::from_str(&upstreams_json).unwrap_or_default();
     let failover = serde_json::from_str(&failover_json).unwrap_or(None);
+    let rewrite = rewrite_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten();
+    let request_headers = request_headers_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten();
+    let response_headers = response_headers_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten();
+    let limits = limits_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten();
+    let timeouts = timeouts_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten();
+    let rate_limit = rate_limit_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten();
+    let hash_key = hash_key_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten();
+    let sticky_session = sticky_session_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten();
+    let mirror = mirror_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten();
+    let split = split_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .flatten();
     let policies = serde_json::from_str(&policies_json).unwrap_or_default();
 
     Ok(RouteSpec {
@@ -126,6 +234,16 @@ fn row_to_route(row: sqlx::sqlite::SqliteRow) -> Result<RouteSpec, sqlx::Error>
         upstreams,
         lb,
         failover,
+        rewrite,
+        request_headers,
+        response_headers,
+        limits,
+        timeouts,
+        rate_limit,
+        hash_key,
+        sticky_session,
+        mirror,
+        split,
         policies,
     })
 }
@@ -1,3 +1,5 @@
+pub mod tls;
+
 use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
@@ -5,7 +7,11 @@ use std::sync::Arc;
 use futures_core::Stream;
 use gateway_proto::config::{
     config_service_server::{ConfigService, ConfigServiceServer},
-    Match, PolicyRef, Route, Snapshot, SubscribeRequest, Upstream,
+    hash_key, header_match, path_match, query_match, rewrite, split_rule, CircuitBreaker, Failover,
+    HashBucket, HashKey, HeaderMatch, HeaderOps, HeaderValue, HealthCheck, Match, Mirror,
+    OutlierDetection, PathMatch, PolicyRef, PrefixRewrite, QueryMatch, RateLimit, RegexRewrite,
+    Rewrite, Route, RouteLimits, Snapshot, SplitRule, StickySession, SubscribeRequest, Timeouts,
+    TlsOverride, TrafficSplit, Upstream, ValueMatch,
 };
 use sqlx::SqlitePool;
 use tokio::sync::watch;
@@ -13,7 +19,14 @@ use tokio_stream::{wrappers::WatchStream, StreamExt};
 use tonic::{Request, Response, Status};
 use tracing::debug;
 
-use crate::model::{RoutePolicy, RouteSpec, Upstream as ModelUpstream};
+use crate::model::{
+    CircuitBreaker as ModelCircuitBreaker, Failover as ModelFailover, HashKey as ModelHashKey,
+    HeaderMatch as ModelHeaderMatch, HeaderOps as ModelHeaderOps, HeaderValue as ModelHeaderValue,
+    HealthCheck as ModelHealthCheck, OutlierDetection as ModelOutlierDetection,
+    PathMatch as ModelPathMatch, QueryMatch as ModelQueryMatch, Rewrite as ModelRewrite,
+    RoutePolicy, RouteSpec, SplitRule as ModelSplitRule, SplitValue as ModelSplitValue,
+    TlsOverride as ModelTlsOverride, TrafficSplit as ModelTrafficSplit, Upstream as ModelUpstream,
+};
 
 #[derive(Clone)]
 pub struct ConfigState {
@@ -65,6 +78,12 @@ impl ConfigState {
     }
 }
 
+impl Default for ConfigState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Clone)]
 pub struct ConfigServiceImpl {
     state: Arc<ConfigState>,
@@ -88,25 +107,192 @@ impl ConfigService for ConfigServiceImpl {
 }
 
 fn route_to_proto(route: RouteSpec) -> Route {
-    let (path_prefix, methods, host) = parse_match(route.match_rules);
     Route {
         id: route.id,
-        r#match: Some(Match {
-            path_prefix,
-            methods,
-            host,
-        }),
+        r#match: Some(parse_match(route.match_rules)),
         upstreams: route.upstreams.into_iter().map(upstream_to_proto).collect(),
         lb: route.lb.unwrap_or_default(),
         policies: route.policies.into_iter().map(policy_to_proto).collect(),
+        failover: route.failover.map(failover_to_proto),
+        rewrite: route.rewrite.map(rewrite_to_proto),
+        request_headers: route.request_headers.map(header_ops_to_proto),
+        response_headers: route.response_headers.map(header_ops_to_proto),
+        limits: route.limits.map(|limits| RouteLimits {
+            max_body_bytes: limits.max_body_bytes.unwrap_or_default(),
+        }),
+        timeouts: route.timeouts.map(|timeouts| Timeouts {
+            connect_ms: timeouts.connect_ms.unwrap_or_default(),
+            read_ms: timeouts.read_ms.unwrap_or_default(),
+            write_ms: timeouts.write_ms.unwrap_or_default(),
+            idle_ms: timeouts.idle_ms.unwrap_or_default(),
+            total_ms: timeouts.total_ms.unwrap_or_default(),
+        }),
+        rate_limit: route.rate_limit.map(|limit| RateLimit {
+            requests_per_second: limit.requests_per_second,
+            burst: limit.burst,
+            key: limit.key.unwrap_or_default(),
+            header: limit.header.unwrap_or_default(),
+        }),
+        hash_key: route.hash_key.and_then(hash_key_to_proto),
+        sticky_session: route.sticky_session.map(|sticky| StickySession {
+            cookie: sticky
+                .cookie
+                .unwrap_or_else(|| "gateway_sticky".to_string()),
+            ttl_ms: sticky.ttl_ms.unwrap_or_default(),
+        }),
+        mirror: route.mirror.map(|mirror| Mirror {
+            url: mirror.url,
+            percent: mirror.percent.unwrap_or(100.0),
+            max_body_bytes: mirror.max_body_bytes.unwrap_or(64 * 1024),
+        }),
+        split: route.split.map(split_to_proto),
+    }
+}
+
+fn header_ops_to_proto(ops: ModelHeaderOps) -> HeaderOps {
+    let values = |values: Vec<ModelHeaderValue>| {
+        values
+            .into_iter()
+            .map(|header| HeaderValue {
+                name: header.name,
+                value: header.value,
+            })
+            .collect()
+    };
+    HeaderOps {
+        add: values(ops.add),
+        set: values(ops.set),
+        remove: ops.remove,
+    }
+}
+
+fn rewrite_to_proto(rewrite: ModelRewrite) -> Rewrite {
+    let path = if let Some(prefix) = rewrite.strip_prefix {
+        Some(rewrite::Path::StripPrefix(prefix))
+    } else if let Some(replace) = rewrite.replace_prefix {
+        Some(rewrite::Path::ReplacePrefix(PrefixRewrite {
+            prefix: replace.prefix,
+            replacement: replace.replacement,
+        }))
+    } else {
+        rewrite.regex.map(|regex| {
+            rewrite::Path::Regex(RegexRewrite {
+                pattern: regex.pattern,
+                substitution: regex.substitution,
+            })
+        })
+    };
+    Rewrite {
+        path,
+        host: rewrite.host.unwrap_or_default(),
+    }
+}
+
+fn hash_key_to_proto(key: ModelHashKey) -> Option<HashKey> {
+    let source = if let Some(name) = key.header {
+        hash_key::Source::Header(name)
+    } else if let Some(name) = key.cookie {
+        hash_key::Source::Cookie(name)
+    } else if key.client_ip {
+        hash_key::Source::ClientIp(true)
+    } else {
+        hash_key::Source::PathSegment(key.path_segment?)
+    };
+    Some(HashKey {
+        source: Some(source),
+    })
+}
+
+fn split_to_proto(split: ModelTrafficSplit) -> TrafficSplit {
+    TrafficSplit {
+        rules: split
+            .rules
+            .into_iter()
+            .filter_map(split_rule_to_proto)
+            .collect(),
+        default_subset: split.default_subset,
+    }
+}
+
+fn split_rule_to_proto(rule: ModelSplitRule) -> Option<SplitRule> {
+    let value_match = |value: ModelSplitValue| ValueMatch {
+        name: value.name,
+        value: value.value.unwrap_or_default(),
+    };
+    let condition = if let Some(header) = rule.header {
+        split_rule::Condition::Header(value_match(header))
+    } else if let Some(cookie) = rule.cookie {
+        split_rule::Condition::Cookie(value_match(cookie))
+    } else {
+        let hash = rule.hash?;
+        split_rule::Condition::Hash(HashBucket {
+            key: hash_key_to_proto(hash.key),
+            percent: hash.percent,
+        })
+    };
+    Some(SplitRule {
+        subset: rule.subset,
+        condition: Some(condition),
+    })
+}
+
+fn failover_to_proto(failover: ModelFailover) -> Failover {
+    Failover {
+        enabled: failover.enabled,
+        max_failovers: failover.max_failovers.unwrap_or(1),
+        retry_on: failover
+            .retry_on
+            .unwrap_or_else(|| vec!["connect_failure".to_string()]),
+        per_try_timeout_ms: failover.per_try_timeout_ms.unwrap_or_default(),
     }
 }
 
 fn upstream_to_proto(upstream: ModelUpstream) -> Upstream {
     Upstream {
         url: upstream.url,
-        weight: upstream.weight.unwrap_or_default(),
+        weight: upstream.weight.unwrap_or(1),
         priority: upstream.priority.unwrap_or_default(),
+        health_check: upstream.health_check.map(health_check_to_proto),
+        outlier_detection: upstream.outlier_detection.map(outlier_detection_to_proto),
+        tls: upstream.tls.map(tls_to_proto),
+        circuit_breaker: upstream.circuit_breaker.map(circuit_breaker_to_proto),
+        subset: upstream.subset.unwrap_or_default(),
+    }
+}
+
+fn tls_to_proto(tls: ModelTlsOverride) -> TlsOverride {
+    TlsOverride {
+        server_name: tls.server_name.unwrap_or_default(),
+        ca_cert_path: tls.ca_cert_path.unwrap_or_default(),
+        insecure_skip_verify: tls.insecure_skip_verify.unwrap_or_default(),
+    }
+}
+
+fn health_check_to_proto(check: ModelHealthCheck) -> HealthCheck {
+    HealthCheck {
+        path: check.path,
+        interval_ms: check.interval_ms,
+        timeout_ms: check.timeout_ms,
+        unhealthy_threshold: check.unhealthy_threshold,
+        healthy_threshold: check.healthy_threshold,
+    }
+}
+
+fn outlier_detection_to_proto(outlier: ModelOutlierDetection) -> OutlierDetection {
+    OutlierDetection {
+        consecutive_5xx: outlier.consecutive_5xx,
+        eject_ms: outlier.eject_ms,
+    }
+}
+
+fn circuit_breaker_to_proto(breaker: ModelCircuitBreaker) -> CircuitBreaker {
+    CircuitBreaker {
+        window_ms: breaker.window_ms,
+        min_requests: breaker.min_requests.unwrap_or(10),
+        error_rate_percent: breaker.error_rate_percent.unwrap_or_default(),
+        latency_ms: breaker.latency_ms.unwrap_or_default(),
+        cooldown_ms: breaker.cooldown_ms,
+        half_open_probes: breaker.half_open_probes.unwrap_or(1),
     }
 }
 
@@ -118,10 +304,11 @@ fn policy_to_proto(policy: RoutePolicy) -> PolicyRef {
     }
 }
 
-fn parse_match(match_rules: serde_json::Value) -> (String, Vec<String>, String) {
+fn parse_match(match_rules: serde_json::Value) -> Match {
     let mut path_prefix = String::new();
     let mut methods = Vec::new();
     let mut host = String::new();
+    let mut hosts = Vec::new();
 
     if let Some(obj) = match_rules.as_object() {
         if let Some(value) = obj.get("path_prefix").and_then(|v| v.as_str()) {
@@ -130,6 +317,9 @@ fn parse_match(match_rules: serde_json::Value) -> (String, Vec<String>, String)
         if let Some(value) = obj.get("host").and_then(|v| v.as_str()) {
             host = value.to_string();
         }
+        if let Some(array) = obj.get("hosts").and_then(|v| v.as_array()) {
+            hosts.extend(array.iter().filter_map(|v| v.as_str()).map(str::to_string));
+        }
         if let Some(array) = obj.get("method").and_then(|v| v.as_array()) {
             for method in array {
                 if let Some(m) = method.as_str() {
@@ -139,5 +329,75 @@ fn parse_match(match_rules: serde_json::Value) -> (String, Vec<String>, String)
         }
     }
 
-    (path_prefix, methods, host)
+    // Routes are validated on write, so malformed header rules do not get here.
+    let headers = ModelHeaderMatch::from_match_rules(&match_rules)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(header_match_to_proto)
+        .collect();
+
+    let query = ModelQueryMatch::from_match_rules(&match_rules)
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(query_match_to_proto)
+        .collect();
+
+    let path = ModelPathMatch::from_match_rules(&match_rules)
+        .ok()
+        .flatten()
+        .and_then(path_match_to_proto);
+
+    Match {
+        path_prefix,
+        methods,
+        host,
+        headers,
+        query,
+        path,
+        hosts,
+    }
+}
+
+fn path_match_to_proto(path: ModelPathMatch) -> Option<PathMatch> {
+    let kind = if let Some(value) = path.exact {
+        path_match::Kind::Exact(value)
+    } else if let Some(value) = path.prefix {
+        path_match::Kind::Prefix(value)
+    } else if let Some(value) = path.regex {
+        path_match::Kind::Regex(value)
+    } else {
+        path_match::Kind::Template(path.template?)
+    };
+    Some(PathMatch { kind: Some(kind) })
+}
+
+fn header_match_to_proto(header: ModelHeaderMatch) -> Option<HeaderMatch> {
+    let rule = if let Some(value) = header.exact {
+        header_match::Rule::Exact(value)
+    } else if let Some(value) = header.prefix {
+        header_match::Rule::Prefix(value)
+    } else if let Some(value) = header.regex {
+        header_match::Rule::Regex(value)
+    } else {
+        header_match::Rule::Present(header.present?)
+    };
+    Some(HeaderMatch {
+        name: header.name.to_ascii_lowercase(),
+        rule: Some(rule),
+        ignore_case: header.ignore_case,
+    })
+}
+
+fn query_match_to_proto(param: ModelQueryMatch) -> Option<QueryMatch> {
+    let rule = if let Some(value) = param.exact {
+        query_match::Rule::Exact(value)
+    } else if let Some(value) = param.regex {
+        query_match::Rule::Regex(value)
+    } else {
+        query_match::Rule::Present(param.present?)
+    };
+    Some(QueryMatch {
+        name: param.name,
+        rule: Some(rule),
+    })
 }
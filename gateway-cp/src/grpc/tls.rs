@@ -0,0 +1,48 @@
+use std::fs;
+use std::io;
+
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+
+use crate::config::GrpcTlsConfig;
+
+/// Builds the server TLS config for the config gRPC endpoint. With a client
+/// CA configured, connections without a certificate signed by it are
+/// rejected during the handshake.
+pub fn server_tls_config(tls: &GrpcTlsConfig) -> io::Result<ServerTlsConfig> {
+    let cert = read_pem("grpc_tls.cert_path", &tls.cert_path)?;
+    let key = read_pem("grpc_tls.key_path", &tls.key_path)?;
+    let mut config = ServerTlsConfig::new().identity(Identity::from_pem(cert, key));
+
+    if let Some(path) = &tls.client_ca_path {
+        let ca = read_pem("grpc_tls.client_ca_path", path)?;
+        config = config.client_ca_root(Certificate::from_pem(ca));
+    }
+    Ok(config)
+}
+
+fn read_pem(field: &str, path: &str) -> io::Result<Vec<u8>> {
+    fs::read(path)
+        .map_err(|err| io::Error::new(err.kind(), format!("cannot read {field} {path}: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_client_ca_names_the_field() {
+        let dir = std::env::temp_dir();
+        let cert = dir.join(format!("gateway-cp-grpc-{}.crt", std::process::id()));
+        fs::write(&cert, "-----BEGIN CERTIFICATE-----\n").unwrap();
+
+        let tls = GrpcTlsConfig {
+            cert_path: cert.display().to_string(),
+            key_path: cert.display().to_string(),
+            client_ca_path: Some("/nonexistent/dp-ca.pem".to_string()),
+        };
+        let err = server_tls_config(&tls).err().unwrap().to_string();
+        assert!(err.contains("grpc_tls.client_ca_path"), "{err}");
+
+        let _ = fs::remove_file(cert);
+    }
+}
@@ -36,9 +36,19 @@ pub async fn run(config: GatewayCpConfig) -> Result<(), Box<dyn std::error::Erro
     let grpc_listener = TcpListener::bind(grpc_addr).await?;
     let grpc_state = state.config_state.clone();
 
+    let mut grpc_builder = GrpcServer::builder();
+    if let Some(tls) = &config.grpc_tls {
+        grpc_builder = grpc_builder.tls_config(grpc::tls::server_tls_config(tls)?)?;
+        tracing::info!(
+            grpc_bind = %config.grpc_bind,
+            mutual = tls.client_ca_path.is_some(),
+            "config gRPC endpoint uses TLS"
+        );
+    }
+
     let grpc = tokio::spawn(async move {
         let server = grpc_state.server();
-        GrpcServer::builder()
+        grpc_builder
             .add_service(server)
             .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(
                 grpc_listener,
@@ -61,6 +71,7 @@ pub async fn start_for_test() -> Result<RunningServer, Box<dyn std::error::Error
             json: true,
         },
         database_url: format!("sqlite://target/gateway-cp-test-{db_suffix}.db"),
+        grpc_tls: None,
     };
 
     reset_test_db(&config.database_url)?;
@@ -233,6 +244,7 @@ fn ensure_sqlite_path(url: &str) -> Result<(), Box<dyn std::error::Error>> {
 
     std::fs::OpenOptions::new()
         .create(true)
+        .truncate(false)
         .write(true)
         .open(file_path)?;
 
@@ -8,6 +8,11 @@ fn default_supported_stages() -> Vec<String> {
     ]
 }
 
+/// Number of set fields among a group of mutually exclusive options.
+fn count_set(fields: &[bool]) -> usize {
+    fields.iter().filter(|set| **set).count()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PolicySpec {
     pub id: String,
@@ -34,9 +39,137 @@ pub struct RouteSpec {
     #[serde(default)]
     pub failover: Option<Failover>,
     #[serde(default)]
+    pub rewrite: Option<Rewrite>,
+    #[serde(default)]
+    pub request_headers: Option<HeaderOps>,
+    #[serde(default)]
+    pub response_headers: Option<HeaderOps>,
+    #[serde(default)]
+    pub limits: Option<RouteLimits>,
+    #[serde(default)]
+    pub timeouts: Option<RouteTimeouts>,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    #[serde(default)]
+    pub hash_key: Option<HashKey>,
+    #[serde(default)]
+    pub sticky_session: Option<StickySession>,
+    #[serde(default)]
+    pub mirror: Option<Mirror>,
+    #[serde(default)]
+    pub split: Option<TrafficSplit>,
+    #[serde(default)]
     pub policies: Vec<RoutePolicy>,
 }
 
+/// `match.path`: exactly one of `exact`, `prefix` (segment-aware), `regex`
+/// or `template` (`/users/{id}/orders`). Replaces the legacy
+/// `match.path_prefix`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PathMatch {
+    #[serde(default)]
+    pub exact: Option<String>,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+impl PathMatch {
+    /// Reads `match.path` from the free-form match rules.
+    pub fn from_match_rules(
+        match_rules: &serde_json::Value,
+    ) -> Result<Option<Self>, serde_json::Error> {
+        match match_rules.get("path") {
+            Some(value) => serde_json::from_value(value.clone()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn rule_count(&self) -> usize {
+        count_set(&[
+            self.exact.is_some(),
+            self.prefix.is_some(),
+            self.regex.is_some(),
+            self.template.is_some(),
+        ])
+    }
+}
+
+/// One entry of `match.headers`. Exactly one of `exact`, `prefix`, `regex`
+/// or `present` must be set; `present: false` requires the header to be
+/// absent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeaderMatch {
+    pub name: String,
+    #[serde(default)]
+    pub exact: Option<String>,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub present: Option<bool>,
+    #[serde(default)]
+    pub ignore_case: bool,
+}
+
+impl HeaderMatch {
+    /// Reads `match.headers` from the free-form match rules.
+    pub fn from_match_rules(
+        match_rules: &serde_json::Value,
+    ) -> Result<Vec<Self>, serde_json::Error> {
+        match match_rules.get("headers") {
+            Some(value) => serde_json::from_value(value.clone()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn rule_count(&self) -> usize {
+        count_set(&[
+            self.exact.is_some(),
+            self.prefix.is_some(),
+            self.regex.is_some(),
+            self.present.is_some(),
+        ])
+    }
+}
+
+/// One entry of `match.query`, evaluated against the decoded query string.
+/// Exactly one of `exact`, `regex` or `present` must be set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryMatch {
+    pub name: String,
+    #[serde(default)]
+    pub exact: Option<String>,
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub present: Option<bool>,
+}
+
+impl QueryMatch {
+    /// Reads `match.query` from the free-form match rules.
+    pub fn from_match_rules(
+        match_rules: &serde_json::Value,
+    ) -> Result<Vec<Self>, serde_json::Error> {
+        match match_rules.get("query") {
+            Some(value) => serde_json::from_value(value.clone()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn rule_count(&self) -> usize {
+        count_set(&[
+            self.exact.is_some(),
+            self.regex.is_some(),
+            self.present.is_some(),
+        ])
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Upstream {
     pub url: String,
@@ -50,11 +183,17 @@ pub struct Upstream {
     pub health_check: Option<HealthCheck>,
     #[serde(default)]
     pub outlier_detection: Option<OutlierDetection>,
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// Subset named by the route's `split` rules.
+    #[serde(default)]
+    pub subset: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TlsOverride {
     pub server_name: Option<String>,
+    /// PEM bundle on the data plane hosts; replaces the system trust store.
     pub ca_cert_path: Option<String>,
     pub insecure_skip_verify: Option<bool>,
 }
@@ -74,6 +213,25 @@ pub struct OutlierDetection {
     pub eject_ms: u64,
 }
 
+/// Opens when, over `window_ms` and at least `min_requests` (default 10),
+/// the 5xx/connect failure rate reaches `error_rate_percent` or the mean
+/// latency exceeds `latency_ms`. After `cooldown_ms`, `half_open_probes`
+/// (default 1) requests are let through; the circuit closes once they all
+/// succeed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreaker {
+    pub window_ms: u64,
+    #[serde(default)]
+    pub min_requests: Option<u32>,
+    #[serde(default)]
+    pub error_rate_percent: Option<u32>,
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    pub cooldown_ms: u64,
+    #[serde(default)]
+    pub half_open_probes: Option<u32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Failover {
     pub enabled: bool,
@@ -85,6 +243,194 @@ pub struct Failover {
     pub per_try_timeout_ms: Option<u64>,
 }
 
+/// Upstream request rewrite. At most one of `strip_prefix`,
+/// `replace_prefix` and `regex` may be set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rewrite {
+    #[serde(default)]
+    pub strip_prefix: Option<String>,
+    #[serde(default)]
+    pub replace_prefix: Option<PrefixRewrite>,
+    #[serde(default)]
+    pub regex: Option<RegexRewrite>,
+    #[serde(default)]
+    pub host: Option<String>,
+}
+
+impl Rewrite {
+    pub fn path_rule_count(&self) -> usize {
+        count_set(&[
+            self.strip_prefix.is_some(),
+            self.replace_prefix.is_some(),
+            self.regex.is_some(),
+        ])
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrefixRewrite {
+    pub prefix: String,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegexRewrite {
+    pub pattern: String,
+    pub substitution: String,
+}
+
+/// Header changes for one direction: `remove`, then `set`, then `add`.
+/// Values may use `${client_ip}`, `${route_id}`, `${request_id}` and
+/// `${path.<name>}` for a variable of the route's path template.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HeaderOps {
+    #[serde(default)]
+    pub add: Vec<HeaderValue>,
+    #[serde(default)]
+    pub set: Vec<HeaderValue>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeaderValue {
+    pub name: String,
+    pub value: String,
+}
+
+/// Per-route overrides of the data plane `[limits]`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteLimits {
+    #[serde(default)]
+    pub max_body_bytes: Option<u64>,
+}
+
+/// Upstream timeouts in milliseconds. `total_ms` bounds the request across
+/// failover attempts until response headers arrive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteTimeouts {
+    #[serde(default)]
+    pub connect_ms: Option<u64>,
+    #[serde(default)]
+    pub read_ms: Option<u64>,
+    #[serde(default)]
+    pub write_ms: Option<u64>,
+    #[serde(default)]
+    pub idle_ms: Option<u64>,
+    #[serde(default)]
+    pub total_ms: Option<u64>,
+}
+
+/// Token bucket enforced by each data plane instance. `key` is `client_ip`
+/// (default), `route`, or `header` together with `header`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    pub burst: u32,
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub header: Option<String>,
+}
+
+/// What `ring_hash` and `maglev` load balancing hash on. Exactly one of
+/// `header`, `cookie`, `client_ip` or `path_segment` (0-based index of a
+/// non-empty path segment) must be set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashKey {
+    #[serde(default)]
+    pub header: Option<String>,
+    #[serde(default)]
+    pub cookie: Option<String>,
+    #[serde(default)]
+    pub client_ip: bool,
+    #[serde(default)]
+    pub path_segment: Option<u32>,
+}
+
+impl HashKey {
+    pub fn source_count(&self) -> usize {
+        count_set(&[
+            self.header.is_some(),
+            self.cookie.is_some(),
+            self.client_ip,
+            self.path_segment.is_some(),
+        ])
+    }
+}
+
+/// Pins clients to the upstream their first request went to, named in a
+/// signed cookie `cookie` (default `gateway_sticky`). `ttl_ms` sets the
+/// cookie's `Max-Age`; without it the cookie lasts for the browser session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StickySession {
+    #[serde(default)]
+    pub cookie: Option<String>,
+    #[serde(default)]
+    pub ttl_ms: Option<u64>,
+}
+
+/// Copies `percent` (default 100) of the route's requests to the shadow
+/// upstream `url` and discards its responses. Requests with bodies over
+/// `max_body_bytes` (default 64 KiB) are not mirrored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Mirror {
+    pub url: String,
+    #[serde(default)]
+    pub percent: Option<f64>,
+    #[serde(default)]
+    pub max_body_bytes: Option<u64>,
+}
+
+/// Rule-based split between named upstream subsets (`upstreams[].subset`).
+/// Rules are tried in order and the first match sends the request to its
+/// `subset`; everything else goes to `default_subset`. When a subset has
+/// no available upstream the default subset is used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficSplit {
+    #[serde(default)]
+    pub rules: Vec<SplitRule>,
+    pub default_subset: String,
+}
+
+/// Exactly one of `header`, `cookie` or `hash` must be set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitRule {
+    pub subset: String,
+    #[serde(default)]
+    pub header: Option<SplitValue>,
+    #[serde(default)]
+    pub cookie: Option<SplitValue>,
+    #[serde(default)]
+    pub hash: Option<SplitHash>,
+}
+
+impl SplitRule {
+    pub fn condition_count(&self) -> usize {
+        count_set(&[
+            self.header.is_some(),
+            self.cookie.is_some(),
+            self.hash.is_some(),
+        ])
+    }
+}
+
+/// A header or cookie equal to `value`, or present at all without one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitValue {
+    pub name: String,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// `percent` of the requests by a stable hash of `key`, e.g. a user id
+/// header, so a user stays on the same side while the percentage holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitHash {
+    pub key: HashKey,
+    pub percent: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoutePolicy {
     pub stage: String, // pre_route | pre_upstream | post_response
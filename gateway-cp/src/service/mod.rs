@@ -21,4 +21,4 @@ impl ValidationError {
 }
 
 pub use policies::validate_policy_spec;
-pub use routes::validate_route_policies;
+pub use routes::{validate_route_policies, validate_route_spec};
@@ -1,13 +1,634 @@
+use gateway_proto::path::PathTemplate;
 use sqlx::SqlitePool;
 
-use crate::{db, model::RouteSpec};
+use crate::{
+    db,
+    model::{
+        CircuitBreaker, HashKey, HeaderMatch, HeaderOps, Mirror, PathMatch, QueryMatch, RateLimit,
+        RouteSpec, RouteTimeouts, SplitValue, StickySession, TrafficSplit,
+    },
+};
 
 use super::{
     merge::deep_merge_default_with_params,
-    validation::{compile_schema, validate_against_schema},
+    validation::{
+        compile_schema, validate_against_schema, ALLOWED_HEADER_VARIABLES, ALLOWED_LB_POLICIES,
+        ALLOWED_RATE_LIMIT_KEYS, ALLOWED_RETRY_ON, HASH_LB_POLICIES, MIN_REQUESTS_PER_SECOND,
+        PROTECTED_HEADERS,
+    },
     ValidationError,
 };
 
+pub fn validate_route_spec(route: &RouteSpec) -> Result<(), ValidationError> {
+    let mut details = Vec::new();
+
+    validate_path_match(route, &mut details);
+    validate_header_matches(route, &mut details);
+    validate_query_matches(route, &mut details);
+    validate_hosts(route, &mut details);
+    validate_rewrite(route, &mut details);
+    validate_lb(route, &mut details);
+    let path_params = path_template_names(route);
+    if route
+        .limits
+        .as_ref()
+        .and_then(|limits| limits.max_body_bytes)
+        .is_some_and(|max| max == 0)
+    {
+        details.push("route.limits.max_body_bytes must be greater than 0".to_string());
+    }
+    if let Some(timeouts) = &route.timeouts {
+        validate_timeouts(timeouts, &mut details);
+    }
+    if let Some(limit) = &route.rate_limit {
+        validate_rate_limit(limit, &mut details);
+    }
+    if let Some(sticky) = &route.sticky_session {
+        validate_sticky_session(sticky, &mut details);
+    }
+    if let Some(mirror) = &route.mirror {
+        validate_mirror(route, mirror, &mut details);
+    }
+    if let Some(split) = &route.split {
+        validate_split(route, split, &mut details);
+    }
+    if let Some(ops) = &route.request_headers {
+        validate_header_ops("route.request_headers", ops, &path_params, &mut details);
+    }
+    if let Some(ops) = &route.response_headers {
+        validate_header_ops("route.response_headers", ops, &path_params, &mut details);
+    }
+
+    for (index, upstream) in route.upstreams.iter().enumerate() {
+        let context = format!("route.upstreams[{index}]");
+
+        if let Some(check) = &upstream.health_check {
+            if !check.path.starts_with('/') {
+                details.push(format!("{context}.health_check.path must start with '/'"));
+            }
+            if check.interval_ms == 0 {
+                details.push(format!(
+                    "{context}.health_check.interval_ms must be greater than 0"
+                ));
+            }
+            if check.timeout_ms == 0 {
+                details.push(format!(
+                    "{context}.health_check.timeout_ms must be greater than 0"
+                ));
+            }
+            if check.healthy_threshold == 0 || check.unhealthy_threshold == 0 {
+                details.push(format!(
+                    "{context}.health_check thresholds must be greater than 0"
+                ));
+            }
+        }
+
+        if let Some(tls) = &upstream.tls {
+            if !upstream.url.starts_with("https://") {
+                details.push(format!("{context}.tls requires an https:// upstream url"));
+            }
+            if tls
+                .server_name
+                .as_deref()
+                .is_some_and(|v| v.trim().is_empty())
+            {
+                details.push(format!("{context}.tls.server_name must not be empty"));
+            }
+            if tls
+                .ca_cert_path
+                .as_deref()
+                .is_some_and(|v| v.trim().is_empty())
+            {
+                details.push(format!("{context}.tls.ca_cert_path must not be empty"));
+            }
+        }
+
+        if let Some(outlier) = &upstream.outlier_detection {
+            if outlier.consecutive_5xx == 0 {
+                details.push(format!(
+                    "{context}.outlier_detection.consecutive_5xx must be greater than 0"
+                ));
+            }
+            if outlier.eject_ms == 0 {
+                details.push(format!(
+                    "{context}.outlier_detection.eject_ms must be greater than 0"
+                ));
+            }
+        }
+
+        if let Some(breaker) = &upstream.circuit_breaker {
+            validate_circuit_breaker(&context, breaker, &mut details);
+        }
+    }
+
+    if let Some(failover) = &route.failover {
+        for value in failover.retry_on.iter().flatten() {
+            if !ALLOWED_RETRY_ON.contains(&value.as_str()) {
+                details.push(format!(
+                    "route.failover.retry_on contains unsupported value {value}"
+                ));
+            }
+        }
+        if failover.per_try_timeout_ms == Some(0) {
+            details.push("route.failover.per_try_timeout_ms must be greater than 0".to_string());
+        }
+    }
+
+    if details.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationError::with_details(details))
+    }
+}
+
+fn validate_path_match(route: &RouteSpec, details: &mut Vec<String>) {
+    let path = match PathMatch::from_match_rules(&route.match_rules) {
+        Ok(Some(path)) => path,
+        Ok(None) => return,
+        Err(err) => {
+            details.push(format!("route.match.path is invalid: {err}"));
+            return;
+        }
+    };
+
+    if route.match_rules.get("path_prefix").is_some() {
+        details.push(
+            "route.match.path and route.match.path_prefix are mutually exclusive".to_string(),
+        );
+    }
+    if path.rule_count() != 1 {
+        details.push(
+            "route.match.path must set exactly one of exact, prefix, regex or template".to_string(),
+        );
+    }
+    for (kind, value) in [("exact", &path.exact), ("prefix", &path.prefix)] {
+        if value.as_ref().is_some_and(|v| !v.starts_with('/')) {
+            details.push(format!("route.match.path.{kind} must start with '/'"));
+        }
+    }
+    if let Some(pattern) = &path.regex {
+        if let Err(err) = regex::Regex::new(pattern) {
+            details.push(format!("route.match.path.regex is invalid: {err}"));
+        }
+    }
+    if let Some(template) = &path.template {
+        if let Err(err) = PathTemplate::parse(template) {
+            details.push(format!("route.match.path.template is invalid: {err}"));
+        }
+    }
+}
+
+fn validate_header_matches(route: &RouteSpec, details: &mut Vec<String>) {
+    let headers = match HeaderMatch::from_match_rules(&route.match_rules) {
+        Ok(headers) => headers,
+        Err(err) => {
+            details.push(format!("route.match.headers is invalid: {err}"));
+            return;
+        }
+    };
+
+    for (index, header) in headers.iter().enumerate() {
+        let context = format!("route.match.headers[{index}]");
+        if header.name.is_empty() || !header.name.bytes().all(is_header_name_byte) {
+            details.push(format!("{context}.name must be a valid header name"));
+        }
+        if header.rule_count() != 1 {
+            details.push(format!(
+                "{context} must set exactly one of exact, prefix, regex or present"
+            ));
+        }
+        if let Some(pattern) = &header.regex {
+            if let Err(err) = regex::Regex::new(pattern) {
+                details.push(format!("{context}.regex is invalid: {err}"));
+            }
+        }
+    }
+}
+
+fn validate_query_matches(route: &RouteSpec, details: &mut Vec<String>) {
+    let params = match QueryMatch::from_match_rules(&route.match_rules) {
+        Ok(params) => params,
+        Err(err) => {
+            details.push(format!("route.match.query is invalid: {err}"));
+            return;
+        }
+    };
+
+    for (index, param) in params.iter().enumerate() {
+        let context = format!("route.match.query[{index}]");
+        if param.name.is_empty() {
+            details.push(format!("{context}.name must not be empty"));
+        }
+        if param.rule_count() != 1 {
+            details.push(format!(
+                "{context} must set exactly one of exact, regex or present"
+            ));
+        }
+        if let Some(pattern) = &param.regex {
+            if let Err(err) = regex::Regex::new(pattern) {
+                details.push(format!("{context}.regex is invalid: {err}"));
+            }
+        }
+    }
+}
+
+fn validate_hosts(route: &RouteSpec, details: &mut Vec<String>) {
+    let Some(rules) = route.match_rules.as_object() else {
+        return;
+    };
+
+    if let Some(host) = rules.get("host") {
+        match host.as_str() {
+            Some(host) => validate_host_pattern("route.match.host", host, details),
+            None => details.push("route.match.host must be a string".to_string()),
+        }
+    }
+
+    let Some(hosts) = rules.get("hosts") else {
+        return;
+    };
+    let Some(hosts) = hosts.as_array() else {
+        details.push("route.match.hosts must be an array of strings".to_string());
+        return;
+    };
+    for (index, host) in hosts.iter().enumerate() {
+        let context = format!("route.match.hosts[{index}]");
+        match host.as_str() {
+            Some(host) => validate_host_pattern(&context, host, details),
+            None => details.push(format!("{context} must be a string")),
+        }
+    }
+}
+
+fn validate_rewrite(route: &RouteSpec, details: &mut Vec<String>) {
+    let Some(rewrite) = &route.rewrite else {
+        return;
+    };
+
+    if rewrite.path_rule_count() > 1 {
+        details.push(
+            "route.rewrite may set only one of strip_prefix, replace_prefix or regex".to_string(),
+        );
+    }
+    if let Some(prefix) = &rewrite.strip_prefix {
+        if !prefix.starts_with('/') {
+            details.push("route.rewrite.strip_prefix must start with '/'".to_string());
+        }
+    }
+    if let Some(replace) = &rewrite.replace_prefix {
+        if !replace.prefix.starts_with('/') {
+            details.push("route.rewrite.replace_prefix.prefix must start with '/'".to_string());
+        }
+        if !replace.replacement.is_empty() && !replace.replacement.starts_with('/') {
+            details.push(
+                "route.rewrite.replace_prefix.replacement must be empty or start with '/'"
+                    .to_string(),
+            );
+        }
+    }
+    if let Some(regex) = &rewrite.regex {
+        if let Err(err) = regex::Regex::new(&regex.pattern) {
+            details.push(format!("route.rewrite.regex.pattern is invalid: {err}"));
+        }
+    }
+    if let Some(host) = &rewrite.host {
+        if host.trim().is_empty() || host.contains(char::is_whitespace) {
+            details.push("route.rewrite.host must be a non-empty host name".to_string());
+        }
+    }
+}
+
+fn validate_lb(route: &RouteSpec, details: &mut Vec<String>) {
+    let lb = route
+        .lb
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if !lb.is_empty() && !ALLOWED_LB_POLICIES.contains(&lb.as_str()) {
+        details.push(format!(
+            "route.lb must be one of {}",
+            ALLOWED_LB_POLICIES.join(", ")
+        ));
+    }
+
+    let hashed = HASH_LB_POLICIES.contains(&lb.as_str());
+    let Some(key) = &route.hash_key else {
+        if hashed {
+            details.push(format!("route.hash_key is required when lb is {lb}"));
+        }
+        return;
+    };
+    if !hashed {
+        details.push(format!(
+            "route.hash_key is only used when lb is one of {}",
+            HASH_LB_POLICIES.join(", ")
+        ));
+    }
+    validate_hash_key("route.hash_key", key, details);
+}
+
+fn validate_hash_key(context: &str, key: &HashKey, details: &mut Vec<String>) {
+    if key.source_count() != 1 {
+        details.push(format!(
+            "{context} must set exactly one of header, cookie, client_ip or path_segment"
+        ));
+    }
+    if key
+        .header
+        .as_deref()
+        .is_some_and(|name| name.is_empty() || !name.bytes().all(is_header_name_byte))
+    {
+        details.push(format!("{context}.header must be a valid header name"));
+    }
+    if key.cookie.as_deref().is_some_and(|name| name.is_empty()) {
+        details.push(format!("{context}.cookie must not be empty"));
+    }
+}
+
+fn validate_circuit_breaker(context: &str, breaker: &CircuitBreaker, details: &mut Vec<String>) {
+    if breaker.window_ms == 0 {
+        details.push(format!(
+            "{context}.circuit_breaker.window_ms must be greater than 0"
+        ));
+    }
+    if breaker.cooldown_ms == 0 {
+        details.push(format!(
+            "{context}.circuit_breaker.cooldown_ms must be greater than 0"
+        ));
+    }
+    if breaker.error_rate_percent.is_none() && breaker.latency_ms.is_none() {
+        details.push(format!(
+            "{context}.circuit_breaker requires error_rate_percent or latency_ms"
+        ));
+    }
+    if breaker
+        .error_rate_percent
+        .is_some_and(|percent| percent == 0 || percent > 100)
+    {
+        details.push(format!(
+            "{context}.circuit_breaker.error_rate_percent must be between 1 and 100"
+        ));
+    }
+    let fields = [
+        ("min_requests", breaker.min_requests.map(u64::from)),
+        ("latency_ms", breaker.latency_ms),
+        ("half_open_probes", breaker.half_open_probes.map(u64::from)),
+    ];
+    for (name, value) in fields {
+        if value == Some(0) {
+            details.push(format!(
+                "{context}.circuit_breaker.{name} must be greater than 0"
+            ));
+        }
+    }
+}
+
+fn validate_rate_limit(limit: &RateLimit, details: &mut Vec<String>) {
+    if !(limit.requests_per_second.is_finite()
+        && limit.requests_per_second >= MIN_REQUESTS_PER_SECOND)
+    {
+        details.push(
+            "route.rate_limit.requests_per_second must allow at least one request per day"
+                .to_string(),
+        );
+    }
+    if limit.burst == 0 {
+        details.push("route.rate_limit.burst must be greater than 0".to_string());
+    }
+    let key = limit.key.as_deref().unwrap_or("client_ip");
+    if !ALLOWED_RATE_LIMIT_KEYS.contains(&key) {
+        details.push(format!(
+            "route.rate_limit.key must be one of {}",
+            ALLOWED_RATE_LIMIT_KEYS.join(", ")
+        ));
+    }
+    match (key, limit.header.as_deref()) {
+        ("header", Some(name)) if !name.is_empty() && name.bytes().all(is_header_name_byte) => {}
+        ("header", _) => details.push(
+            "route.rate_limit.header must be a valid header name when key is \"header\""
+                .to_string(),
+        ),
+        (_, Some(_)) => {
+            details.push("route.rate_limit.header is only used when key is \"header\"".to_string())
+        }
+        _ => {}
+    }
+}
+
+fn validate_sticky_session(sticky: &StickySession, details: &mut Vec<String>) {
+    if sticky
+        .cookie
+        .as_deref()
+        .is_some_and(|name| name.is_empty() || !name.bytes().all(is_header_name_byte))
+    {
+        details.push("route.sticky_session.cookie must be a valid cookie name".to_string());
+    }
+    if sticky.ttl_ms.is_some_and(|ttl| ttl < 1000) {
+        details.push("route.sticky_session.ttl_ms must be at least 1000".to_string());
+    }
+}
+
+fn validate_mirror(route: &RouteSpec, mirror: &Mirror, details: &mut Vec<String>) {
+    if !(mirror.url.starts_with("http://") || mirror.url.starts_with("https://")) {
+        details.push("route.mirror.url must be an http:// or https:// url".to_string());
+    }
+    if route
+        .upstreams
+        .iter()
+        .any(|upstream| upstream.url == mirror.url)
+    {
+        details.push("route.mirror.url must not be one of the route's upstreams".to_string());
+    }
+    if mirror
+        .percent
+        .is_some_and(|percent| !(percent > 0.0 && percent <= 100.0))
+    {
+        details.push("route.mirror.percent must be greater than 0 and at most 100".to_string());
+    }
+    if mirror.max_body_bytes == Some(0) {
+        details.push("route.mirror.max_body_bytes must be greater than 0".to_string());
+    }
+}
+
+fn validate_split(route: &RouteSpec, split: &TrafficSplit, details: &mut Vec<String>) {
+    let mut subsets = Vec::new();
+    for (index, upstream) in route.upstreams.iter().enumerate() {
+        match upstream.subset.as_deref() {
+            Some(subset) if !subset.trim().is_empty() => subsets.push(subset),
+            _ => details.push(format!(
+                "route.upstreams[{index}].subset is required when route.split is set"
+            )),
+        }
+    }
+    if !subsets.contains(&split.default_subset.as_str()) {
+        details.push("route.split.default_subset must name an upstream subset".to_string());
+    }
+
+    for (index, rule) in split.rules.iter().enumerate() {
+        let context = format!("route.split.rules[{index}]");
+        if !subsets.contains(&rule.subset.as_str()) {
+            details.push(format!("{context}.subset must name an upstream subset"));
+        }
+        if rule.condition_count() != 1 {
+            details.push(format!(
+                "{context} must set exactly one of header, cookie or hash"
+            ));
+        }
+        if let Some(header) = &rule.header {
+            if !is_valid_split_name(header) {
+                details.push(format!("{context}.header.name must be a valid header name"));
+            }
+        }
+        if let Some(cookie) = &rule.cookie {
+            if !is_valid_split_name(cookie) {
+                details.push(format!("{context}.cookie.name must be a valid cookie name"));
+            }
+        }
+        if let Some(hash) = &rule.hash {
+            validate_hash_key(&format!("{context}.hash.key"), &hash.key, details);
+            if !(0.0..=100.0).contains(&hash.percent) {
+                details.push(format!("{context}.hash.percent must be between 0 and 100"));
+            }
+        }
+    }
+}
+
+fn is_valid_split_name(value: &SplitValue) -> bool {
+    !value.name.is_empty() && value.name.bytes().all(is_header_name_byte)
+}
+
+fn validate_timeouts(timeouts: &RouteTimeouts, details: &mut Vec<String>) {
+    let fields = [
+        ("connect_ms", timeouts.connect_ms),
+        ("read_ms", timeouts.read_ms),
+        ("write_ms", timeouts.write_ms),
+        ("idle_ms", timeouts.idle_ms),
+        ("total_ms", timeouts.total_ms),
+    ];
+    for (name, value) in fields {
+        if value == Some(0) {
+            details.push(format!("route.timeouts.{name} must be greater than 0"));
+        }
+    }
+    if let Some(total) = timeouts.total_ms {
+        for (name, value) in [
+            ("connect_ms", timeouts.connect_ms),
+            ("read_ms", timeouts.read_ms),
+        ] {
+            if value.is_some_and(|value| value > total) {
+                details.push(format!(
+                    "route.timeouts.{name} must not exceed route.timeouts.total_ms"
+                ));
+            }
+        }
+    }
+}
+
+fn validate_header_ops(
+    context: &str,
+    ops: &HeaderOps,
+    path_params: &[String],
+    details: &mut Vec<String>,
+) {
+    let names = ops
+        .add
+        .iter()
+        .map(|header| ("add", header.name.as_str()))
+        .chain(ops.set.iter().map(|header| ("set", header.name.as_str())))
+        .chain(ops.remove.iter().map(|name| ("remove", name.as_str())));
+    for (action, name) in names {
+        if name.is_empty() || !name.bytes().all(is_header_name_byte) {
+            details.push(format!(
+                "{context}.{action}: {name:?} is not a valid header name"
+            ));
+        } else if PROTECTED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            details.push(format!("{context}.{action}: {name} may not be changed"));
+        }
+    }
+
+    for (action, header) in ops
+        .add
+        .iter()
+        .map(|header| ("add", header))
+        .chain(ops.set.iter().map(|header| ("set", header)))
+    {
+        if header
+            .value
+            .bytes()
+            .any(|b| b == b'\r' || b == b'\n' || b == 0)
+        {
+            details.push(format!(
+                "{context}.{action}: value for {} contains control characters",
+                header.name
+            ));
+        }
+        if let Err(err) = validate_header_template(&header.value, path_params) {
+            details.push(format!(
+                "{context}.{action}: value for {} {err}",
+                header.name
+            ));
+        }
+    }
+}
+
+/// Checks `${name}` placeholders against [`ALLOWED_HEADER_VARIABLES`] and
+/// `${path.<name>}` against the variables of the route's path template.
+fn validate_header_template(value: &str, path_params: &[String]) -> Result<(), String> {
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| "has an unterminated variable".to_string())?;
+        let name = &after[..end];
+        let known = match name.strip_prefix("path.") {
+            Some(param) => path_params.iter().any(|known| known == param),
+            None => ALLOWED_HEADER_VARIABLES.contains(&name),
+        };
+        if !known {
+            return Err(format!(
+                "uses unknown variable ${{{name}}}; expected one of {} or path.<name> for a path template variable",
+                ALLOWED_HEADER_VARIABLES.join(", ")
+            ));
+        }
+        rest = &after[end + 1..];
+    }
+    Ok(())
+}
+
+/// Variables of the route's path template, if it has a valid one.
+fn path_template_names(route: &RouteSpec) -> Vec<String> {
+    PathMatch::from_match_rules(&route.match_rules)
+        .ok()
+        .flatten()
+        .and_then(|path| path.template)
+        .and_then(|template| PathTemplate::parse(&template).ok())
+        .map(|template| template.names().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Accepts `api.example.com`, `*.example.com` and `.example.com`.
+fn validate_host_pattern(context: &str, pattern: &str, details: &mut Vec<String>) {
+    let domain = pattern
+        .strip_prefix("*.")
+        .or_else(|| pattern.strip_prefix('.'))
+        .unwrap_or(pattern);
+    if domain.is_empty() {
+        details.push(format!("{context} must not be empty"));
+    } else if domain.contains('*') {
+        details.push(format!(
+            "{context} may only use a wildcard as a leading \"*.\" label"
+        ));
+    } else if domain.starts_with('.') || domain.ends_with('.') || domain.contains("..") {
+        details.push(format!("{context} has an empty label"));
+    }
+}
+
+fn is_header_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
 pub async fn validate_route_policies(
     pool: &SqlitePool,
     route: &RouteSpec,
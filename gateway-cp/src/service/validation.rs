@@ -5,6 +5,37 @@ use super::ValidationError;
 
 pub const ALLOWED_POLICY_STAGES: [&str; 3] = ["pre_route", "pre_upstream", "post_response"];
 
+pub const ALLOWED_RETRY_ON: [&str; 3] = ["connect_failure", "5xx", "timeout"];
+
+pub const ALLOWED_LB_POLICIES: [&str; 7] = [
+    "round_robin",
+    "weighted_round_robin",
+    "weighted",
+    "ring_hash",
+    "maglev",
+    "least_request",
+    "peak_ewma",
+];
+
+/// Load balancing policies that pick upstreams by `route.hash_key`.
+pub const HASH_LB_POLICIES: [&str; 2] = ["ring_hash", "maglev"];
+
+/// Lowest accepted `rate_limit.requests_per_second`: one request a day.
+pub const MIN_REQUESTS_PER_SECOND: f64 = 1.0 / 86_400.0;
+
+pub const ALLOWED_RATE_LIMIT_KEYS: [&str; 3] = ["client_ip", "header", "route"];
+
+pub const ALLOWED_HEADER_VARIABLES: [&str; 3] = ["client_ip", "route_id", "request_id"];
+
+/// Framing and hop-by-hop headers that route header rules may not touch.
+pub const PROTECTED_HEADERS: [&str; 5] = [
+    "connection",
+    "content-length",
+    "keep-alive",
+    "transfer-encoding",
+    "upgrade",
+];
+
 pub fn validate_supported_stages(stages: &[String], context: &str) -> Result<(), ValidationError> {
     if stages.is_empty() {
         return Err(ValidationError::new(format!(
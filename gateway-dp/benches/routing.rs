@@ -0,0 +1,35 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use gateway_dp::bench::RoutingFixture;
+
+fn match_route(c: &mut Criterion) {
+    let mut group = c.benchmark_group("match_route");
+    for routes in [10, 1_000, 10_000] {
+        let fixture = RoutingFixture::new(routes);
+        for request in 0..fixture.requests() {
+            assert_eq!(
+                fixture.match_indexed(request),
+                fixture.match_linear(request),
+                "index and linear matcher disagree"
+            );
+        }
+
+        group.bench_with_input(BenchmarkId::new("indexed", routes), &fixture, |b, f| {
+            let mut request = 0;
+            b.iter(|| {
+                request = (request + 1) % f.requests();
+                black_box(f.match_indexed(black_box(request)));
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("linear", routes), &fixture, |b, f| {
+            let mut request = 0;
+            b.iter(|| {
+                request = (request + 1) % f.requests();
+                black_box(f.match_linear(black_box(request)));
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, match_route);
+criterion_main!(benches);
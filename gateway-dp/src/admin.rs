@@ -0,0 +1,114 @@
+use async_trait::async_trait;
+use http::{header, Response, StatusCode};
+use pingora::apps::http_app::ServeHttp;
+use pingora::protocols::http::ServerSession;
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::state::State;
+
+/// Read-only operational endpoints on `admin.bind`. `GET /upstreams` lists
+/// every known upstream with its health, ejection, circuit state and
+/// in-flight request count.
+pub struct AdminApp {
+    state: Arc<State>,
+}
+
+#[derive(Debug, Serialize)]
+struct UpstreamView {
+    url: String,
+    available: bool,
+    healthy: bool,
+    ejected: bool,
+    circuit: &'static str,
+    in_flight: u64,
+}
+
+impl AdminApp {
+    pub fn new(state: Arc<State>) -> Self {
+        Self { state }
+    }
+
+    fn upstreams(&self) -> Vec<UpstreamView> {
+        self.state
+            .upstreams()
+            .entries()
+            .into_iter()
+            .map(|(url, status)| UpstreamView {
+                url,
+                available: status.is_available(),
+                healthy: status.is_healthy(),
+                ejected: status.is_ejected(),
+                circuit: status.circuit().state().as_str(),
+                in_flight: status.in_flight(),
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl ServeHttp for AdminApp {
+    async fn response(&self, session: &mut ServerSession) -> Response<Vec<u8>> {
+        let request = session.req_header();
+        match (request.method.as_str(), request.uri.path()) {
+            ("GET", "/upstreams") => json_response(StatusCode::OK, &self.upstreams()),
+            _ => json_response(
+                StatusCode::NOT_FOUND,
+                &serde_json::json!({ "error": "not_found" }),
+            ),
+        }
+    }
+}
+
+fn json_response(status: StatusCode, body: &impl Serialize) -> Response<Vec<u8>> {
+    let body = serde_json::to_vec(body).unwrap_or_default();
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .expect("static response parts are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::RouteSnapshot;
+
+    #[test]
+    fn lists_upstreams_by_url() {
+        let state = Arc::new(State::new(RouteSnapshot::empty()));
+        state
+            .upstreams()
+            .status("http://10.0.0.2:8080")
+            .set_healthy(false);
+        state
+            .upstreams()
+            .status("http://10.0.0.1:8080")
+            .start_request();
+
+        let upstreams = AdminApp::new(state).upstreams();
+        let json = serde_json::to_value(&upstreams).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {
+                    "url": "http://10.0.0.1:8080",
+                    "available": true,
+                    "healthy": true,
+                    "ejected": false,
+                    "circuit": "closed",
+                    "in_flight": 1
+                },
+                {
+                    "url": "http://10.0.0.2:8080",
+                    "available": false,
+                    "healthy": false,
+                    "ejected": false,
+                    "circuit": "closed",
+                    "in_flight": 0
+                }
+            ])
+        );
+    }
+}
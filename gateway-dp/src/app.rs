@@ -1,4 +1,7 @@
-use crate::{config::GatewayDpConfig, proxy::GatewayProxy, router::RouteSnapshot, state::State};
+use crate::{
+    admin::AdminApp, config::GatewayDpConfig, errors::ErrorPages, proxy::GatewayProxy,
+    router::RouteSnapshot, state::State, sticky::CookieSigner,
+};
 use pingora::prelude::*;
 use std::sync::Arc;
 use tracing::info;
@@ -7,20 +10,72 @@ pub fn run(config: GatewayDpConfig) {
     crate::logging::init(&config.logging.level, config.logging.json);
     let snapshot = RouteSnapshot::empty();
     let state = Arc::new(State::new(snapshot));
-    let proxy = GatewayProxy::new(state.clone());
+    let error_pages = ErrorPages::new(config.listener.error_body.clone());
+    let cookie_signer = match &config.sticky_sessions {
+        Some(sticky) => CookieSigner::new(sticky.secret.as_bytes()),
+        None => {
+            info!("no sticky_sessions.secret configured, sticky cookies are only honored by this process");
+            CookieSigner::random()
+        }
+    };
+    let proxy = GatewayProxy::new(
+        state.clone(),
+        error_pages,
+        config.limits.max_body_bytes,
+        config.limits.pre_upstream_body_bytes,
+        cookie_signer,
+    );
 
     let mut server = Server::new(None).unwrap();
     server.bootstrap();
 
     let mut svc = http_proxy_service(&server.configuration, proxy);
-    svc.add_tcp(&config.listener.bind);
-    info!(bind = %config.listener.bind, "gateway-dp listening");
+    match &config.listener.tls {
+        Some(tls) => {
+            let settings = crate::tls::listener_settings(tls)
+                .unwrap_or_else(|err| panic!("failed to configure listener TLS: {err}"));
+            svc.add_tls_with_settings(&config.listener.bind, None, settings);
+            info!(bind = %config.listener.bind, cert = %tls.cert_path, "gateway-dp listening with TLS");
+        }
+        None => {
+            svc.add_tcp(&config.listener.bind);
+            info!(bind = %config.listener.bind, "gateway-dp listening");
+        }
+    }
 
-    let cp_sync =
-        crate::sync::CpSync::new(config.control_plane.grpc_endpoint.clone(), state.clone());
+    let cp_tls = config.control_plane.tls.as_ref().map(|tls| {
+        crate::tls::control_plane_client(tls)
+            .unwrap_or_else(|err| panic!("failed to configure control plane TLS: {err}"))
+    });
+    let cp_sync = crate::sync::CpSync::new(
+        config.control_plane.grpc_endpoint.clone(),
+        cp_tls,
+        state.clone(),
+    );
     let bg = background_service("cp-sync", cp_sync);
+    let health = background_service(
+        "health-check",
+        crate::health::HealthChecker::new(state.clone()),
+    );
 
     server.add_service(svc);
     server.add_service(bg);
+    server.add_service(health);
+
+    if let Some(metrics) = &config.metrics {
+        let mut prometheus = pingora::services::listening::Service::prometheus_http_service();
+        prometheus.add_tcp(&metrics.bind);
+        info!(bind = %metrics.bind, "gateway-dp metrics listening");
+        server.add_service(prometheus);
+    }
+    if let Some(admin) = &config.admin {
+        let mut admin_svc = pingora::services::listening::Service::new(
+            "admin".to_string(),
+            AdminApp::new(state.clone()),
+        );
+        admin_svc.add_tcp(&admin.bind);
+        info!(bind = %admin.bind, "gateway-dp admin listening");
+        server.add_service(admin_svc);
+    }
     server.run_forever();
 }
@@ -0,0 +1,77 @@
+//! Fixtures for the benchmarks in `benches/`. Not a supported API.
+
+use pingora::http::RequestHeader;
+
+use crate::router::{self, PathMatcher, PathTemplate, Route, RouteSnapshot, Upstream};
+
+/// A synthetic snapshot shaped like a multi-tenant config: routes spread
+/// over many hosts, mostly prefixes with some exact, templated and
+/// host-less routes, plus a request mix of hits and misses.
+pub struct RoutingFixture {
+    snapshot: RouteSnapshot,
+    requests: Vec<RequestHeader>,
+}
+
+impl RoutingFixture {
+    pub fn new(route_count: usize) -> Self {
+        let hosts = (route_count / 20).max(1);
+        let routes = (0..route_count)
+            .map(|i| {
+                let host = (i % 10 != 0).then(|| format!("tenant{}.example.com", i % hosts));
+                let methods = if i % 3 == 0 {
+                    vec!["GET".to_string()]
+                } else {
+                    Vec::new()
+                };
+                let mut route = Route::new(
+                    format!("route-{i:05}"),
+                    None,
+                    methods,
+                    host,
+                    vec![Upstream::new("http://127.0.0.1:9000".to_string())],
+                );
+                route.path = Some(match i % 10 {
+                    0..=6 => PathMatcher::Prefix(format!("/svc{}/v{}", i / 10, i % 3)),
+                    7 | 8 => PathMatcher::Exact(format!("/svc{}/health", i / 10)),
+                    _ => PathMatcher::Template(
+                        PathTemplate::parse(&format!("/svc{}/items/{{id}}", i / 10)).unwrap(),
+                    ),
+                });
+                route
+            })
+            .collect();
+
+        let requests = (0..64)
+            .map(|i| {
+                let svc = (i * 7919) % (route_count / 10).max(1);
+                let path = match i % 4 {
+                    0 => format!("/svc{svc}/v1/orders/{i}"),
+                    1 => format!("/svc{svc}/health"),
+                    2 => format!("/svc{svc}/items/{i}"),
+                    _ => format!("/missing/{i}"),
+                };
+                let mut request = RequestHeader::build("GET", path.as_bytes(), None).unwrap();
+                let host = format!("tenant{}.example.com", i % hosts);
+                request.insert_header("host", host).unwrap();
+                request
+            })
+            .collect();
+
+        Self {
+            snapshot: RouteSnapshot::new(routes),
+            requests,
+        }
+    }
+
+    pub fn requests(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn match_indexed(&self, request: usize) -> Option<&str> {
+        router::match_route(&self.snapshot, &self.requests[request]).map(|r| r.id.as_str())
+    }
+
+    pub fn match_linear(&self, request: usize) -> Option<&str> {
+        router::match_route_linear(&self.snapshot, &self.requests[request]).map(|r| r.id.as_str())
+    }
+}
@@ -0,0 +1,445 @@
+use pingora::prelude::*;
+use pingora::ErrorSource;
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::metrics::UPSTREAM_CIRCUIT_OPENS;
+use crate::router::{CircuitBreaker, Upstream};
+
+/// The rolling window is kept as this many buckets; the oldest one drops
+/// out as a whole once it is a full window old.
+const WINDOW_BUCKETS: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Open => "open",
+            Self::HalfOpen => "half_open",
+        }
+    }
+}
+
+/// Circuit breaker state for one upstream. Kept in
+/// [`UpstreamStatus`](crate::upstream::UpstreamStatus), so it is shared by
+/// every route using the URL and survives config swaps.
+#[derive(Debug, Default)]
+pub struct Circuit {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    phase: Phase,
+    window: Window,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+enum Phase {
+    #[default]
+    Closed,
+    Open {
+        until: Instant,
+        cooldown: Duration,
+        probes: u32,
+    },
+    HalfOpen {
+        since: Instant,
+        cooldown: Duration,
+        probes: u32,
+        admitted: u32,
+        succeeded: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Transition {
+    Opened,
+    Closed,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    buckets: VecDeque<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    start: Instant,
+    requests: u32,
+    failures: u32,
+    latency: Duration,
+}
+
+impl Circuit {
+    pub fn state(&self) -> CircuitState {
+        self.state_at(Instant::now())
+    }
+
+    /// Whether a request may be sent: always while closed, never while
+    /// open, and while half-open only until the probe budget is used up.
+    pub fn allows(&self) -> bool {
+        self.allows_at(Instant::now())
+    }
+
+    /// Counts a request sent to the upstream against the half-open probe
+    /// budget. Does nothing in the other states.
+    pub fn on_attempt(&self) {
+        self.on_attempt_at(Instant::now());
+    }
+
+    fn state_at(&self, now: Instant) -> CircuitState {
+        let mut inner = self.lock();
+        inner.advance(now);
+        match inner.phase {
+            Phase::Closed => CircuitState::Closed,
+            Phase::Open { .. } => CircuitState::Open,
+            Phase::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    fn allows_at(&self, now: Instant) -> bool {
+        let mut inner = self.lock();
+        inner.advance(now);
+        match inner.phase {
+            Phase::Closed => true,
+            Phase::Open { .. } => false,
+            Phase::HalfOpen {
+                probes, admitted, ..
+            } => admitted < probes,
+        }
+    }
+
+    fn on_attempt_at(&self, now: Instant) {
+        let mut inner = self.lock();
+        inner.advance(now);
+        if let Phase::HalfOpen { admitted, .. } = &mut inner.phase {
+            *admitted += 1;
+        }
+    }
+
+    fn record_at(
+        &self,
+        config: &CircuitBreaker,
+        failed: bool,
+        latency: Duration,
+        now: Instant,
+    ) -> Option<Transition> {
+        let mut guard = self.lock();
+        let inner = &mut *guard;
+        inner.advance(now);
+        match &mut inner.phase {
+            Phase::Closed => {
+                inner.window.record(config.window, failed, latency, now);
+                if !inner.window.trips(config) {
+                    return None;
+                }
+                inner.phase = Phase::open(config, now);
+                inner.window.buckets.clear();
+                Some(Transition::Opened)
+            }
+            Phase::HalfOpen {
+                probes, succeeded, ..
+            } => {
+                let slow = config.latency.is_some_and(|max| latency > max);
+                if failed || slow {
+                    inner.phase = Phase::open(config, now);
+                    return Some(Transition::Opened);
+                }
+                *succeeded += 1;
+                if *succeeded < *probes {
+                    return None;
+                }
+                inner.phase = Phase::Closed;
+                Some(Transition::Closed)
+            }
+            // Responses to requests sent before the circuit opened.
+            Phase::Open { .. } => None,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Inner {
+    fn advance(&mut self, now: Instant) {
+        match self.phase {
+            Phase::Open {
+                until,
+                cooldown,
+                probes,
+            } if now >= until => self.phase = Phase::half_open(cooldown, probes, now),
+            // Probes that never report back, e.g. because the client went
+            // away, would otherwise hold the circuit half-open forever.
+            Phase::HalfOpen {
+                since,
+                cooldown,
+                probes,
+                ..
+            } if now.saturating_duration_since(since) >= cooldown => {
+                self.phase = Phase::half_open(cooldown, probes, now)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Phase {
+    fn open(config: &CircuitBreaker, now: Instant) -> Self {
+        Self::Open {
+            until: now + config.cooldown,
+            cooldown: config.cooldown,
+            probes: config.half_open_probes.max(1),
+        }
+    }
+
+    fn half_open(cooldown: Duration, probes: u32, now: Instant) -> Self {
+        Self::HalfOpen {
+            since: now,
+            cooldown,
+            probes,
+            admitted: 0,
+            succeeded: 0,
+        }
+    }
+}
+
+impl Window {
+    fn record(&mut self, span: Duration, failed: bool, latency: Duration, now: Instant) {
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| now.saturating_duration_since(bucket.start) >= span)
+        {
+            self.buckets.pop_front();
+        }
+
+        let width = span / WINDOW_BUCKETS;
+        let stale = self
+            .buckets
+            .back()
+            .is_none_or(|bucket| now.saturating_duration_since(bucket.start) >= width);
+        if stale {
+            self.buckets.push_back(Bucket {
+                start: now,
+                requests: 0,
+                failures: 0,
+                latency: Duration::ZERO,
+            });
+        }
+        let bucket = self
+            .buckets
+            .back_mut()
+            .expect("window has a current bucket");
+        bucket.requests += 1;
+        bucket.failures += u32::from(failed);
+        bucket.latency += latency;
+    }
+
+    fn trips(&self, config: &CircuitBreaker) -> bool {
+        let requests: u32 = self.buckets.iter().map(|b| b.requests).sum();
+        if requests == 0 || requests < config.min_requests {
+            return false;
+        }
+        let failures: u32 = self.buckets.iter().map(|b| b.failures).sum();
+        let latency: Duration = self.buckets.iter().map(|b| b.latency).sum();
+
+        let error_rate = config.error_rate_percent > 0
+            && u64::from(failures) * 100
+                >= u64::from(config.error_rate_percent) * u64::from(requests);
+        let slow = config.latency.is_some_and(|max| latency / requests > max);
+        error_rate || slow
+    }
+}
+
+/// Records a response from `upstream`; 5xx responses count as failures.
+pub fn record_response(upstream: &Upstream, status: u16, latency: Duration) {
+    record(upstream, status >= 500, latency);
+}
+
+/// Records a connect failure, timeout or other error talking to `upstream`.
+pub fn record_failure(upstream: &Upstream, latency: Duration) {
+    record(upstream, true, latency);
+}
+
+/// Whether an error while proxying counts against the upstream: anything
+/// raised on its side of the exchange, such as a reset connection or a
+/// timeout. Errors from the client or the gateway's own checks do not.
+pub fn is_upstream_failure(e: &Error) -> bool {
+    match e.esource() {
+        ErrorSource::Upstream => true,
+        ErrorSource::Downstream => false,
+        _ => matches!(
+            e.etype(),
+            ErrorType::ReadTimedout | ErrorType::WriteTimedout
+        ),
+    }
+}
+
+fn record(upstream: &Upstream, failed: bool, latency: Duration) {
+    let Some(config) = &upstream.circuit_breaker else {
+        return;
+    };
+    let transition = upstream
+        .status
+        .circuit()
+        .record_at(config, failed, latency, Instant::now());
+    match transition {
+        Some(Transition::Opened) => {
+            UPSTREAM_CIRCUIT_OPENS
+                .with_label_values(&[upstream.url.as_str()])
+                .inc();
+            warn!(
+                upstream = %upstream.url,
+                cooldown_ms = config.cooldown.as_millis() as u64,
+                "upstream circuit opened"
+            );
+        }
+        Some(Transition::Closed) => info!(upstream = %upstream.url, "upstream circuit closed"),
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OK: Duration = Duration::from_millis(10);
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker {
+            window: Duration::from_secs(10),
+            min_requests: 4,
+            error_rate_percent: 50,
+            latency: Some(Duration::from_millis(500)),
+            cooldown: Duration::from_secs(5),
+            half_open_probes: 2,
+        }
+    }
+
+    #[test]
+    fn opens_on_error_rate_after_min_requests() {
+        let circuit = Circuit::default();
+        let config = breaker();
+        let now = Instant::now();
+
+        assert_eq!(circuit.record_at(&config, true, OK, now), None);
+        assert_eq!(circuit.record_at(&config, true, OK, now), None);
+        assert_eq!(circuit.record_at(&config, false, OK, now), None);
+        assert!(circuit.allows_at(now));
+
+        assert_eq!(
+            circuit.record_at(&config, false, OK, now),
+            Some(Transition::Opened)
+        );
+        assert_eq!(circuit.state_at(now), CircuitState::Open);
+        assert!(!circuit.allows_at(now));
+    }
+
+    #[test]
+    fn opens_on_mean_latency() {
+        let circuit = Circuit::default();
+        let config = breaker();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            circuit.record_at(&config, false, Duration::from_millis(400), now);
+        }
+        assert_eq!(
+            circuit.record_at(&config, false, Duration::from_secs(1), now),
+            Some(Transition::Opened)
+        );
+    }
+
+    #[test]
+    fn failures_age_out_of_the_window() {
+        let circuit = Circuit::default();
+        let config = breaker();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            circuit.record_at(&config, true, OK, start);
+        }
+        let later = start + Duration::from_secs(11);
+        for _ in 0..3 {
+            assert_eq!(circuit.record_at(&config, false, OK, later), None);
+        }
+        assert_eq!(
+            circuit.record_at(&config, true, OK, later),
+            None,
+            "only 1 of 4 requests in the window failed"
+        );
+    }
+
+    #[test]
+    fn half_open_admits_probes_and_closes_on_success() {
+        let circuit = Circuit::default();
+        let config = breaker();
+        let start = Instant::now();
+        for _ in 0..4 {
+            circuit.record_at(&config, true, OK, start);
+        }
+
+        let after_cooldown = start + config.cooldown;
+        assert_eq!(circuit.state_at(after_cooldown), CircuitState::HalfOpen);
+        circuit.on_attempt_at(after_cooldown);
+        assert!(circuit.allows_at(after_cooldown));
+        circuit.on_attempt_at(after_cooldown);
+        assert!(!circuit.allows_at(after_cooldown));
+
+        assert_eq!(circuit.record_at(&config, false, OK, after_cooldown), None);
+        assert_eq!(
+            circuit.record_at(&config, false, OK, after_cooldown),
+            Some(Transition::Closed)
+        );
+        assert!(circuit.allows_at(after_cooldown));
+    }
+
+    #[test]
+    fn upstream_side_errors_are_failures() {
+        let reset = Error::new(ErrorType::ConnectionClosed).into_up();
+        assert!(is_upstream_failure(&reset));
+        assert!(is_upstream_failure(&Error::new(ErrorType::ReadTimedout)));
+
+        let client_gone = Error::new(ErrorType::ConnectionClosed).into_down();
+        assert!(!is_upstream_failure(&client_gone));
+        assert!(!is_upstream_failure(
+            &Error::new(ErrorType::WriteTimedout).into_down()
+        ));
+        assert!(!is_upstream_failure(&Error::new(ErrorType::Custom(
+            "request body too large"
+        ))));
+    }
+
+    #[test]
+    fn failed_probe_reopens() {
+        let circuit = Circuit::default();
+        let config = breaker();
+        let start = Instant::now();
+        for _ in 0..4 {
+            circuit.record_at(&config, true, OK, start);
+        }
+
+        let after_cooldown = start + config.cooldown;
+        circuit.on_attempt_at(after_cooldown);
+        assert_eq!(
+            circuit.record_at(&config, false, Duration::from_secs(1), after_cooldown),
+            Some(Transition::Opened)
+        );
+        assert!(!circuit.allows_at(after_cooldown));
+        assert!(circuit.allows_at(after_cooldown + config.cooldown));
+    }
+}
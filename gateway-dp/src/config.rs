@@ -7,7 +7,11 @@ pub struct GatewayDpConfig {
     pub listener: ListenerConfig,
     pub control_plane: ControlPlaneConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
     pub limits: LimitsConfig,
+    pub metrics: Option<MetricsConfig>,
+    pub admin: Option<AdminConfig>,
+    pub sticky_sessions: Option<StickySessionsConfig>,
 }
 
 #[allow(dead_code)]
@@ -15,6 +19,10 @@ pub struct GatewayDpConfig {
 pub struct ListenerConfig {
     pub bind: String,
     pub tls: Option<TlsConfig>,
+    /// Body of locally generated error responses. `${status}`, `${code}`,
+    /// `${message}` and `${request_id}` are substituted; the default is a
+    /// JSON object with `error`, `message` and `request_id`.
+    pub error_body: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -28,7 +36,19 @@ pub struct TlsConfig {
 #[derive(Debug, Deserialize)]
 pub struct ControlPlaneConfig {
     pub grpc_endpoint: String,
-    pub tls: Option<TlsConfig>,
+    pub tls: Option<ControlPlaneTlsConfig>,
+}
+
+/// TLS for the config channel. `ca_path` pins the CA that signed the control
+/// plane certificate; `cert_path`/`key_path` are the client identity
+/// presented for mutual TLS.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct ControlPlaneTlsConfig {
+    pub ca_path: String,
+    pub cert_path: Option<String>,
+    pub key_path: Option<String>,
+    pub server_name: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -46,14 +66,59 @@ pub struct RollingFileConfig {
     pub prefix: String,
 }
 
+/// `max_body_bytes` caps request bodies (routes may override it);
+/// `pre_upstream_body_bytes` caps how much of a body is held before the
+/// upstream has it: longer bodies are neither replayed on failover nor
+/// mirrored.
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct LimitsConfig {
+    #[serde(default = "default_max_body_bytes")]
     pub max_body_bytes: u64,
+    #[serde(default = "default_pre_upstream_body_bytes")]
     pub pre_upstream_body_bytes: u64,
 }
 
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: default_max_body_bytes(),
+            pre_upstream_body_bytes: default_pre_upstream_body_bytes(),
+        }
+    }
+}
+
+fn default_max_body_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_pre_upstream_body_bytes() -> u64 {
+    64 * 1024
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct MetricsConfig {
+    pub bind: String,
+}
+
+/// Listener for the read-only admin endpoints, e.g. `GET /upstreams`.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct AdminConfig {
+    pub bind: String,
+}
+
+/// Key for signing sticky session cookies. Data planes serving the same
+/// routes must share it; without it each process uses a random key.
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct StickySessionsConfig {
+    pub secret: String,
+}
+
 impl GatewayDpConfig {
+    #[allow(clippy::result_large_err)]
     pub fn load(path: &str) -> Result<Self, figment::Error> {
         Figment::new()
             .merge(Toml::file(path))
@@ -0,0 +1,259 @@
+use bytes::Bytes;
+use pingora::prelude::*;
+use pingora::ErrorSource;
+
+/// Error type returned by `upstream_peer` when every upstream of the route
+/// is unhealthy, ejected or already tried.
+pub const NO_HEALTHY_UPSTREAM: ErrorType = ErrorType::Custom("no healthy upstream");
+
+/// Error type returned once the route's total timeout has passed, either
+/// before an upstream attempt or while its response is read.
+pub const DEADLINE_EXCEEDED: ErrorType = ErrorType::Custom("request deadline exceeded");
+
+/// Error type returned by `request_body_filter` once a streamed body grows
+/// past the route's limit.
+pub const BODY_TOO_LARGE: ErrorType = ErrorType::Custom("request body too large");
+
+/// A response generated by the gateway itself. `code` is part of the API:
+/// clients may match on it, so existing values must not change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalResponse {
+    pub status: u16,
+    pub code: &'static str,
+    pub message: &'static str,
+}
+
+pub const ROUTE_NOT_FOUND: LocalResponse = LocalResponse {
+    status: 404,
+    code: "route_not_found",
+    message: "no route matches the request",
+};
+
+pub const PAYLOAD_TOO_LARGE: LocalResponse = LocalResponse {
+    status: 413,
+    code: "payload_too_large",
+    message: "the request body exceeds the size limit",
+};
+
+pub const TOO_MANY_REQUESTS: LocalResponse = LocalResponse {
+    status: 429,
+    code: "rate_limited",
+    message: "too many requests",
+};
+
+const NO_UPSTREAM: LocalResponse = LocalResponse {
+    status: 503,
+    code: "no_healthy_upstream",
+    message: "no healthy upstream is available for the route",
+};
+
+const CONNECT_FAILED: LocalResponse = LocalResponse {
+    status: 502,
+    code: "upstream_connect_failed",
+    message: "could not connect to the upstream",
+};
+
+const UPSTREAM_TIMEOUT: LocalResponse = LocalResponse {
+    status: 504,
+    code: "upstream_timeout",
+    message: "the upstream did not respond in time",
+};
+
+const BAD_GATEWAY: LocalResponse = LocalResponse {
+    status: 502,
+    code: "bad_gateway",
+    message: "the upstream sent an invalid response",
+};
+
+const BAD_REQUEST: LocalResponse = LocalResponse {
+    status: 400,
+    code: "bad_request",
+    message: "the request could not be processed",
+};
+
+const INTERNAL: LocalResponse = LocalResponse {
+    status: 500,
+    code: "internal_error",
+    message: "the gateway failed to process the request",
+};
+
+impl LocalResponse {
+    /// The response for a proxy error, or `None` when the downstream
+    /// connection is already gone and nothing can be sent.
+    pub fn for_error(e: &Error) -> Option<Self> {
+        use ErrorType::*;
+
+        if e.etype() == &NO_HEALTHY_UPSTREAM {
+            return Some(NO_UPSTREAM);
+        }
+        if e.etype() == &BODY_TOO_LARGE {
+            return Some(PAYLOAD_TOO_LARGE);
+        }
+        if e.etype() == &DEADLINE_EXCEEDED {
+            return Some(UPSTREAM_TIMEOUT);
+        }
+        let response = match (e.esource(), e.etype()) {
+            (_, HTTPStatus(status)) if *status < 500 => LocalResponse {
+                status: *status,
+                ..BAD_REQUEST
+            },
+            (_, HTTPStatus(status)) => LocalResponse {
+                status: *status,
+                ..BAD_GATEWAY
+            },
+            (ErrorSource::Upstream, ConnectTimedout | ReadTimedout | WriteTimedout) => {
+                UPSTREAM_TIMEOUT
+            }
+            (ErrorSource::Upstream, TLSHandshakeTimedout) => UPSTREAM_TIMEOUT,
+            (
+                ErrorSource::Upstream,
+                ConnectRefused | ConnectNoRoute | ConnectError | ConnectProxyFailure
+                | TLSHandshakeFailure | InvalidCert | HandshakeError | SocketError,
+            ) => CONNECT_FAILED,
+            (ErrorSource::Upstream, _) => BAD_GATEWAY,
+            (ErrorSource::Downstream, WriteError | ReadError | ConnectionClosed) => return None,
+            (ErrorSource::Downstream, _) => BAD_REQUEST,
+            (ErrorSource::Internal | ErrorSource::Unset, _) => INTERNAL,
+        };
+        Some(response)
+    }
+}
+
+/// Renders local error bodies from the listener's template. Placeholders
+/// `${status}`, `${code}`, `${message}` and `${request_id}` are replaced
+/// with JSON-escaped values, so they belong inside string literals.
+#[derive(Clone, Debug)]
+pub struct ErrorPages {
+    template: String,
+}
+
+const DEFAULT_TEMPLATE: &str =
+    r#"{"error":"${code}","message":"${message}","request_id":"${request_id}"}"#;
+
+impl Default for ErrorPages {
+    fn default() -> Self {
+        Self {
+            template: DEFAULT_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl ErrorPages {
+    pub fn new(template: Option<String>) -> Self {
+        template
+            .map(|template| Self { template })
+            .unwrap_or_default()
+    }
+
+    pub fn render(&self, response: &LocalResponse, request_id: &str) -> String {
+        self.template
+            .replace("${status}", &response.status.to_string())
+            .replace("${code}", &json_escape(response.code))
+            .replace("${message}", &json_escape(response.message))
+            .replace("${request_id}", &json_escape(request_id))
+    }
+
+    /// Sends `response` downstream with any extra `headers`, closing the
+    /// connection afterwards as pingora does for its own error responses.
+    pub async fn send(
+        &self,
+        session: &mut Session,
+        response: &LocalResponse,
+        request_id: &str,
+        headers: &[(&'static str, String)],
+    ) -> Result<()> {
+        let body = self.render(response, request_id);
+        let mut header = ResponseHeader::build(response.status, Some(3))?;
+        header.insert_header("content-type", "application/json")?;
+        header.insert_header("content-length", body.len().to_string())?;
+        if !request_id.is_empty() {
+            header.insert_header("x-request-id", request_id)?;
+        }
+        for (name, value) in headers {
+            header.insert_header(*name, value)?;
+        }
+        session
+            .as_mut()
+            .write_error_response(header, Bytes::from(body))
+            .await
+    }
+}
+
+fn json_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upstream_error(etype: ErrorType) -> Box<Error> {
+        let mut e = Error::new(etype);
+        e.esource = ErrorSource::Upstream;
+        e
+    }
+
+    #[test]
+    fn maps_proxy_errors_to_local_responses() {
+        let status = |e: Box<Error>| LocalResponse::for_error(&e).map(|r| (r.status, r.code));
+
+        assert_eq!(
+            status(Error::new(NO_HEALTHY_UPSTREAM)),
+            Some((503, "no_healthy_upstream"))
+        );
+        assert_eq!(
+            status(Error::new(BODY_TOO_LARGE)),
+            Some((413, "payload_too_large"))
+        );
+        assert_eq!(
+            status(Error::new(DEADLINE_EXCEEDED)),
+            Some((504, "upstream_timeout"))
+        );
+        assert_eq!(
+            status(upstream_error(ErrorType::ConnectRefused)),
+            Some((502, "upstream_connect_failed"))
+        );
+        assert_eq!(
+            status(upstream_error(ErrorType::ReadTimedout)),
+            Some((504, "upstream_timeout"))
+        );
+        assert_eq!(
+            status(upstream_error(ErrorType::InvalidHTTPHeader)),
+            Some((502, "bad_gateway"))
+        );
+
+        let mut closed = Error::new(ErrorType::ConnectionClosed);
+        closed.esource = ErrorSource::Downstream;
+        assert_eq!(status(closed), None);
+    }
+
+    #[test]
+    fn default_body_is_json_with_code_and_request_id() {
+        let body = ErrorPages::default().render(&ROUTE_NOT_FOUND, "req-\"1\"");
+        assert_eq!(
+            body,
+            r#"{"error":"route_not_found","message":"no route matches the request","request_id":"req-\"1\""}"#
+        );
+    }
+
+    #[test]
+    fn custom_template() {
+        let pages = ErrorPages::new(Some(r#"{"status":${status},"code":"${code}"}"#.to_string()));
+        assert_eq!(
+            pages.render(&ROUTE_NOT_FOUND, "req-1"),
+            r#"{"status":404,"code":"route_not_found"}"#
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use async_trait::async_trait;
+use pingora::connectors::http::Connector;
+use pingora::prelude::*;
+use pingora::server::ShutdownWatch;
+use pingora::services::background::BackgroundService;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+use tracing::{debug, info, warn};
+
+use crate::proxy::build_peer;
+use crate::router::{HealthCheck, RouteSnapshot, Timeouts, Upstream};
+use crate::state::State;
+use crate::upstream::UpstreamStatus;
+
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Actively probes upstreams that carry a [`HealthCheck`] and records the
+/// result on their shared [`UpstreamStatus`]. One probe task runs per
+/// upstream URL; tasks are reconciled against the current snapshot so a
+/// config swap only restarts probes whose settings changed.
+pub struct HealthChecker {
+    state: Arc<State>,
+    connector: Arc<Connector>,
+}
+
+struct ProbeTask {
+    check: HealthCheck,
+    status: Arc<UpstreamStatus>,
+    handle: JoinHandle<()>,
+}
+
+impl HealthChecker {
+    pub fn new(state: Arc<State>) -> Self {
+        Self {
+            state,
+            connector: Arc::new(Connector::new(None)),
+        }
+    }
+
+    fn reconcile(&self, snapshot: &RouteSnapshot, tasks: &mut HashMap<String, ProbeTask>) {
+        let targets = collect_targets(snapshot);
+
+        tasks.retain(|url, task| {
+            let keep = targets
+                .get(url)
+                .is_some_and(|upstream| upstream.health_check.as_ref() == Some(&task.check));
+            if !keep {
+                task.handle.abort();
+                if !targets.contains_key(url) {
+                    // Without a check nothing would ever mark it healthy again.
+                    task.status.set_healthy(true);
+                }
+            }
+            keep
+        });
+
+        for (url, upstream) in targets {
+            if tasks.contains_key(&url) {
+                continue;
+            }
+            let Some(check) = upstream.health_check.clone() else {
+                continue;
+            };
+            debug!(upstream = %url, path = %check.path, "starting health check");
+            let status = upstream.status.clone();
+            let handle = tokio::spawn(probe_loop(self.connector.clone(), upstream, check.clone()));
+            tasks.insert(
+                url,
+                ProbeTask {
+                    check,
+                    status,
+                    handle,
+                },
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl BackgroundService for HealthChecker {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let mut tasks: HashMap<String, ProbeTask> = HashMap::new();
+        let mut applied: Option<Arc<RouteSnapshot>> = None;
+
+        loop {
+            let snapshot = self.state.snapshot();
+            if !applied.as_ref().is_some_and(|a| Arc::ptr_eq(a, &snapshot)) {
+                self.reconcile(&snapshot, &mut tasks);
+                applied = Some(snapshot);
+            }
+
+            tokio::select! {
+                _ = shutdown.changed() => break,
+                _ = sleep(RECONCILE_INTERVAL) => {}
+            }
+        }
+
+        for task in tasks.into_values() {
+            task.handle.abort();
+        }
+    }
+}
+
+/// Upstreams with a health check, keyed by URL. When several routes check
+/// the same URL the first route (by id) wins.
+fn collect_targets(snapshot: &RouteSnapshot) -> HashMap<String, Upstream> {
+    let mut targets = HashMap::new();
+    for route in &snapshot.routes {
+        for upstream in &route.upstreams {
+            if upstream.health_check.is_some() {
+                targets
+                    .entry(upstream.url.clone())
+                    .or_insert_with(|| upstream.clone());
+            }
+        }
+    }
+    targets
+}
+
+async fn probe_loop(connector: Arc<Connector>, upstream: Upstream, check: HealthCheck) {
+    let peer = match build_peer(&upstream, &Timeouts::default()) {
+        Ok(peer) => peer,
+        Err(err) => {
+            warn!(upstream = %upstream.url, error = %err, "cannot health check upstream");
+            return;
+        }
+    };
+    let mut counter = HealthCounter::default();
+
+    loop {
+        let ok = probe(&connector, &peer, &check).await;
+        if let Some(healthy) = counter.record(ok, &check) {
+            apply_transition(&upstream.url, &upstream.status, healthy);
+        }
+        sleep(check.interval).await;
+    }
+}
+
+fn apply_transition(url: &str, status: &UpstreamStatus, healthy: bool) {
+    if status.set_healthy(healthy) {
+        if healthy {
+            info!(upstream = %url, "upstream marked healthy");
+        } else {
+            warn!(upstream = %url, "upstream marked unhealthy");
+        }
+    }
+}
+
+/// Sends `GET <path>` and treats any 2xx answer within the timeout as a pass.
+async fn probe(connector: &Connector, peer: &HttpPeer, check: &HealthCheck) -> bool {
+    let attempt = async {
+        let (mut session, _) = connector.get_http_session(peer).await?;
+        let mut request = RequestHeader::build("GET", check.path.as_bytes(), None)?;
+        request.insert_header("Host", peer.sni.as_str())?;
+        request.insert_header("User-Agent", "yali-health-check")?;
+        session.write_request_header(Box::new(request)).await?;
+        session.finish_request_body().await?;
+        session.read_response_header().await?;
+        let status = session.response_header().map(|resp| resp.status.as_u16());
+        session.shutdown().await;
+        Ok::<_, Box<Error>>(status)
+    };
+
+    match timeout(check.timeout, attempt).await {
+        Ok(Ok(Some(status))) => (200..300).contains(&status),
+        Ok(Ok(None)) => false,
+        Ok(Err(err)) => {
+            debug!(peer = %peer, error = %err, "health check failed");
+            false
+        }
+        Err(_) => {
+            debug!(peer = %peer, "health check timed out");
+            false
+        }
+    }
+}
+
+/// Consecutive pass/fail counters. `record` returns the new health state
+/// once a threshold is crossed.
+#[derive(Debug, Default)]
+struct HealthCounter {
+    successes: u32,
+    failures: u32,
+}
+
+impl HealthCounter {
+    fn record(&mut self, ok: bool, check: &HealthCheck) -> Option<bool> {
+        if ok {
+            self.failures = 0;
+            self.successes = self.successes.saturating_add(1);
+            (self.successes == check.healthy_threshold).then_some(true)
+        } else {
+            self.successes = 0;
+            self.failures = self.failures.saturating_add(1);
+            (self.failures == check.unhealthy_threshold).then_some(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::Route;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn check(healthy: u32, unhealthy: u32) -> HealthCheck {
+        HealthCheck {
+            path: "/healthz".to_string(),
+            interval: Duration::from_millis(50),
+            timeout: Duration::from_millis(500),
+            unhealthy_threshold: unhealthy,
+            healthy_threshold: healthy,
+        }
+    }
+
+    #[test]
+    fn counter_flips_after_consecutive_results() {
+        let check = check(2, 3);
+        let mut counter = HealthCounter::default();
+
+        assert_eq!(counter.record(false, &check), None);
+        assert_eq!(counter.record(false, &check), None);
+        assert_eq!(counter.record(true, &check), None);
+        assert_eq!(counter.record(false, &check), None);
+        assert_eq!(counter.record(false, &check), None);
+        assert_eq!(counter.record(false, &check), Some(false));
+        assert_eq!(counter.record(false, &check), None);
+
+        assert_eq!(counter.record(true, &check), None);
+        assert_eq!(counter.record(true, &check), Some(true));
+        assert_eq!(counter.record(true, &check), None);
+    }
+
+    #[test]
+    fn targets_are_deduplicated_by_url() {
+        let checked = Upstream {
+            health_check: Some(check(1, 1)),
+            ..Upstream::new("http://10.0.0.1:8080".to_string())
+        };
+        let unchecked = Upstream::new("http://10.0.0.2:8080".to_string());
+        let snapshot = RouteSnapshot::new(vec![
+            Route::new(
+                "a".to_string(),
+                None,
+                Vec::new(),
+                None,
+                vec![checked.clone(), unchecked],
+            ),
+            Route::new("b".to_string(), None, Vec::new(), None, vec![checked]),
+        ]);
+
+        let targets = collect_targets(&snapshot);
+        assert_eq!(targets.len(), 1);
+        assert!(targets.contains_key("http://10.0.0.1:8080"));
+    }
+
+    async fn serve_once(listener: &TcpListener, response: &str) {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 1024];
+        let _ = socket.read(&mut buf).await.unwrap();
+        socket.write_all(response.as_bytes()).await.unwrap();
+        let _ = socket.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn probe_reports_status_class() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let upstream = Upstream::new(format!("http://{addr}"));
+        let peer = build_peer(&upstream, &Timeouts::default()).unwrap();
+        let connector = Connector::new(None);
+        let check = check(1, 1);
+
+        let server = tokio::spawn(async move {
+            serve_once(&listener, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").await;
+            serve_once(
+                &listener,
+                "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+            )
+            .await;
+        });
+
+        assert!(probe(&connector, &peer, &check).await);
+        assert!(!probe(&connector, &peer, &check).await);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn probe_fails_when_nothing_listens() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let upstream = Upstream::new(format!("http://{addr}"));
+        let peer = build_peer(&upstream, &Timeouts::default()).unwrap();
+
+        assert!(!probe(&Connector::new(None), &peer, &check(1, 1)).await);
+    }
+}
@@ -1,11 +1,25 @@
 pub mod config;
 
+#[doc(hidden)]
+pub mod bench;
+
+mod admin;
 mod app;
+mod circuit;
+mod errors;
+mod health;
 mod logging;
+mod metrics;
+mod mirror;
+mod outlier;
 mod proxy;
+mod ratelimit;
 mod router;
 mod state;
+mod sticky;
 mod sync;
+mod tls;
+mod upstream;
 
 pub use config::GatewayDpConfig;
 
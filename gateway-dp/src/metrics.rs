@@ -0,0 +1,38 @@
+use prometheus::{register_int_counter_vec, IntCounterVec};
+use std::sync::LazyLock;
+
+pub static UPSTREAM_EJECTIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "gateway_upstream_ejections_total",
+        "Upstreams ejected by passive outlier detection",
+        &["upstream"]
+    )
+    .expect("register gateway_upstream_ejections_total")
+});
+
+pub static UPSTREAM_READMISSIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "gateway_upstream_readmissions_total",
+        "Ejected upstreams returned to the load balancing pool",
+        &["upstream"]
+    )
+    .expect("register gateway_upstream_readmissions_total")
+});
+
+pub static UPSTREAM_CIRCUIT_OPENS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "gateway_upstream_circuit_opens_total",
+        "Upstream circuit breakers tripped open",
+        &["upstream"]
+    )
+    .expect("register gateway_upstream_circuit_opens_total")
+});
+
+pub static MIRROR_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "gateway_mirror_requests_total",
+        "Requests copied to route mirrors, by outcome",
+        &["route", "outcome"]
+    )
+    .expect("register gateway_mirror_requests_total")
+});
@@ -0,0 +1,240 @@
+use bytes::{Bytes, BytesMut};
+use pingora::connectors::http::Connector;
+use pingora::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::timeout;
+use tracing::debug;
+
+use crate::metrics::MIRROR_REQUESTS;
+use crate::proxy::build_peer;
+use crate::router::{Timeouts, Upstream};
+
+/// Mirrored requests in flight per process. Past this new copies are
+/// dropped, so a slow shadow cannot pile up tasks and memory.
+const MAX_IN_FLIGHT: usize = 256;
+/// Bound on a whole mirrored exchange, response included.
+const MIRROR_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a drained shadow connection is kept for reuse.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Copies a share of a route's requests to a shadow upstream. Responses
+/// are read and discarded; the client only ever sees the primary's.
+#[derive(Clone, Debug)]
+pub struct Mirror {
+    pub upstream: Upstream,
+    /// Share of requests mirrored, in percent.
+    pub percent: f64,
+    /// Requests with larger bodies are not mirrored; the listener's
+    /// `limits.pre_upstream_body_bytes` caps it as well.
+    pub max_body_bytes: u64,
+}
+
+impl Mirror {
+    /// Whether a request is mirrored, given a random `draw` made by the
+    /// gateway. Nothing the client sends, such as `x-request-id`, may feed
+    /// it, or clients could choose to be mirrored or not.
+    pub fn samples(&self, draw: u64) -> bool {
+        ((draw % 10_000) as f64) < self.percent * 100.0
+    }
+}
+
+/// A request being copied for the route's mirror while it is proxied.
+#[derive(Debug)]
+pub struct MirrorCapture {
+    route_id: String,
+    mirror: Mirror,
+    /// The mirror's body cap, lowered to the listener's buffering limit.
+    max_body_bytes: u64,
+    header: Option<RequestHeader>,
+    body: BytesMut,
+    oversized: bool,
+}
+
+impl MirrorCapture {
+    pub fn new(route_id: &str, mirror: &Mirror, buffer_limit: u64) -> Self {
+        Self {
+            route_id: route_id.to_string(),
+            mirror: mirror.clone(),
+            max_body_bytes: mirror.max_body_bytes.min(buffer_limit),
+            header: None,
+            body: BytesMut::new(),
+            oversized: false,
+        }
+    }
+
+    /// Starts over with the request as sent to the upstream, after
+    /// rewrites and header changes. Failover attempts replay the body from
+    /// the start, so what was captured so far is dropped.
+    pub fn start(&mut self, header: &RequestHeader) {
+        self.header = Some(header.clone());
+        self.body.clear();
+        self.oversized = false;
+    }
+
+    pub fn push_body(&mut self, chunk: &[u8]) {
+        if self.oversized {
+            return;
+        }
+        if (self.body.len() + chunk.len()) as u64 > self.max_body_bytes {
+            self.oversized = true;
+            self.body = BytesMut::new();
+            return;
+        }
+        self.body.extend_from_slice(chunk);
+    }
+}
+
+/// Sends captured requests to mirrors on background tasks.
+pub struct MirrorSender {
+    connector: Arc<Connector>,
+    permits: Arc<Semaphore>,
+}
+
+impl MirrorSender {
+    pub fn new() -> Self {
+        Self {
+            connector: Arc::new(Connector::new(None)),
+            permits: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+        }
+    }
+
+    /// Hands `capture` to a background task and returns at once; nothing
+    /// about the mirror can delay or fail the primary request.
+    pub fn send(&self, capture: MirrorCapture) {
+        let MirrorCapture {
+            route_id,
+            mirror,
+            header,
+            max_body_bytes: _,
+            body,
+            oversized,
+        } = capture;
+        let record = move |outcome: &str| {
+            MIRROR_REQUESTS
+                .with_label_values(&[route_id.as_str(), outcome])
+                .inc();
+        };
+        let Some(header) = header else {
+            return;
+        };
+        if oversized {
+            record("body_too_large");
+            return;
+        }
+        let Ok(permit) = Arc::clone(&self.permits).try_acquire_owned() else {
+            record("dropped");
+            return;
+        };
+
+        let connector = Arc::clone(&self.connector);
+        tokio::spawn(async move {
+            let _permit = permit;
+            let exchange = exchange(&connector, &mirror.upstream, header, body.freeze());
+            match timeout(MIRROR_TIMEOUT, exchange).await {
+                Ok(Ok(_)) => record("sent"),
+                Ok(Err(err)) => {
+                    debug!(mirror = %mirror.upstream.url, error = %err, "mirrored request failed");
+                    record("failed");
+                }
+                Err(_) => {
+                    debug!(mirror = %mirror.upstream.url, "mirrored request timed out");
+                    record("failed");
+                }
+            }
+        });
+    }
+}
+
+impl Default for MirrorSender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sends one request and discards the response, returning its status.
+async fn exchange(
+    connector: &Connector,
+    upstream: &Upstream,
+    header: RequestHeader,
+    body: Bytes,
+) -> Result<u16> {
+    let peer = build_peer(upstream, &Timeouts::default())?;
+    let (mut session, _) = connector.get_http_session(&peer).await?;
+    session.write_request_header(Box::new(header)).await?;
+    if !body.is_empty() {
+        session.write_request_body(body, true).await?;
+    }
+    session.finish_request_body().await?;
+    session.read_response_header().await?;
+    let status = session
+        .response_header()
+        .map(|resp| resp.status.as_u16())
+        .unwrap_or_default();
+    while session.read_response_body().await?.is_some() {}
+    connector
+        .release_http_session(session, &peer, Some(IDLE_TIMEOUT))
+        .await;
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::hash_bytes;
+
+    fn mirror(percent: f64, max_body_bytes: u64) -> Mirror {
+        Mirror {
+            upstream: Upstream::new("http://10.0.0.9:8080".to_string()),
+            percent,
+            max_body_bytes,
+        }
+    }
+
+    #[test]
+    fn samples_the_configured_share_of_requests() {
+        let draws: Vec<u64> = (0..10_000)
+            .map(|idx| hash_bytes(format!("draw-{idx}").as_bytes()))
+            .collect();
+        let sampled = |mirror: &Mirror| draws.iter().filter(|draw| mirror.samples(**draw)).count();
+
+        assert_eq!(sampled(&mirror(0.0, 0)), 0);
+        assert_eq!(sampled(&mirror(100.0, 0)), draws.len());
+        assert!(sampled(&mirror(10.0, 0)).abs_diff(1_000) < 150);
+    }
+
+    #[test]
+    fn oversized_bodies_are_not_kept() {
+        let mut capture = MirrorCapture::new("orders", &mirror(100.0, 8), u64::MAX);
+        let header = RequestHeader::build("POST", b"/orders", None).unwrap();
+        capture.start(&header);
+        capture.push_body(b"12345");
+        assert_eq!(&capture.body[..], b"12345");
+
+        capture.push_body(b"6789");
+        assert!(capture.oversized);
+        assert!(capture.body.is_empty());
+    }
+
+    #[test]
+    fn listener_buffer_limit_caps_the_mirror_body() {
+        let mut capture = MirrorCapture::new("orders", &mirror(100.0, 8), 4);
+        let header = RequestHeader::build("POST", b"/orders", None).unwrap();
+        capture.start(&header);
+        capture.push_body(b"12345");
+        assert!(capture.oversized);
+    }
+
+    #[test]
+    fn failover_restarts_the_capture() {
+        let mut capture = MirrorCapture::new("orders", &mirror(100.0, 8), u64::MAX);
+        let header = RequestHeader::build("POST", b"/orders", None).unwrap();
+        capture.start(&header);
+        capture.push_body(b"1234");
+
+        capture.start(&header);
+        capture.push_body(b"1234");
+        assert_eq!(&capture.body[..], b"1234");
+    }
+}
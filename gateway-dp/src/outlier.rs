@@ -0,0 +1,109 @@
+use std::sync::Arc;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::metrics::{UPSTREAM_EJECTIONS, UPSTREAM_READMISSIONS};
+use crate::router::Upstream;
+
+/// Passive outlier detection: upstreams that return `consecutive_5xx` 5xx
+/// responses or connect failures in a row are ejected for `eject`.
+pub fn record_status(upstream: &Upstream, status: u16) {
+    if status >= 500 {
+        record_failure(upstream, "5xx");
+    } else {
+        upstream.status.record_success();
+    }
+}
+
+pub fn record_connect_failure(upstream: &Upstream) {
+    record_failure(upstream, "connect_failure");
+}
+
+fn record_failure(upstream: &Upstream, reason: &str) {
+    let Some(outlier) = &upstream.outlier_detection else {
+        return;
+    };
+    let failures = upstream.status.record_failure();
+    if failures < outlier.consecutive_5xx {
+        return;
+    }
+
+    upstream.status.record_success();
+    upstream.status.eject_for(outlier.eject);
+    UPSTREAM_EJECTIONS
+        .with_label_values(&[upstream.url.as_str()])
+        .inc();
+    warn!(
+        upstream = %upstream.url,
+        failures,
+        reason,
+        eject_ms = outlier.eject.as_millis() as u64,
+        "upstream ejected"
+    );
+
+    let url = upstream.url.clone();
+    let status = Arc::clone(&upstream.status);
+    let eject = outlier.eject;
+    tokio::spawn(async move {
+        sleep(eject).await;
+        // A later ejection extends the window and schedules its own readmission.
+        if !status.is_ejected() {
+            UPSTREAM_READMISSIONS
+                .with_label_values(&[url.as_str()])
+                .inc();
+            info!(upstream = %url, "upstream readmitted");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::OutlierDetection;
+    use std::time::Duration;
+
+    fn upstream(consecutive_5xx: u32) -> Upstream {
+        Upstream {
+            outlier_detection: Some(OutlierDetection {
+                consecutive_5xx,
+                eject: Duration::from_millis(50),
+            }),
+            ..Upstream::new("http://10.0.0.1:8080".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn ejects_after_consecutive_failures() {
+        let upstream = upstream(3);
+
+        record_status(&upstream, 502);
+        record_connect_failure(&upstream);
+        assert!(upstream.status.is_available());
+
+        record_status(&upstream, 503);
+        assert!(upstream.status.is_ejected());
+
+        sleep(Duration::from_millis(80)).await;
+        assert!(upstream.status.is_available());
+    }
+
+    #[tokio::test]
+    async fn success_resets_the_failure_streak() {
+        let upstream = upstream(2);
+
+        record_status(&upstream, 500);
+        record_status(&upstream, 404);
+        record_status(&upstream, 500);
+        assert!(upstream.status.is_available());
+    }
+
+    #[test]
+    fn ignores_failures_without_outlier_detection() {
+        let upstream = Upstream::new("http://10.0.0.1:8080".to_string());
+
+        for _ in 0..10 {
+            record_status(&upstream, 500);
+        }
+        assert!(upstream.status.is_available());
+    }
+}
@@ -1,82 +1,661 @@
 use async_trait::async_trait;
+use bytes::Bytes;
 use pingora::prelude::*;
+use pingora::proxy::FailToProxy;
 use std::sync::Arc;
-use tracing::{debug, warn};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
 use url::Url;
+use uuid::Uuid;
 
-use crate::{router, state::State};
+use crate::errors::{self, ErrorPages, LocalResponse};
+use crate::mirror::{MirrorCapture, MirrorSender};
+use crate::ratelimit::{Decision, RateLimitKey};
+use crate::router::{self, HeaderVars, RetryReason, Timeouts};
+use crate::sticky::{self, CookieSigner};
+use crate::{circuit, outlier, state::State};
+
+/// Longer client-supplied request ids are replaced rather than trusted.
+const MAX_REQUEST_ID_LEN: usize = 128;
 
 pub struct GatewayProxy {
     state: Arc<State>,
+    error_pages: ErrorPages,
+    /// Request body limit for routes without their own.
+    max_body_bytes: u64,
+    /// Longest request body kept for failover replays and mirrors.
+    pre_upstream_body_bytes: u64,
+    cookie_signer: CookieSigner,
+    mirrors: MirrorSender,
+}
+
+#[derive(Default)]
+pub struct RequestCtx {
+    route: Option<router::Route>,
+    upstream: Option<router::Upstream>,
+    /// Upstreams already attempted for this request, in order.
+    tried: Vec<String>,
+    failovers: u32,
+    /// Set when an upstream 5xx was turned into an error to trigger a retry.
+    retry_5xx: bool,
+    /// Variables captured by a templated route path, e.g. `id` for
+    /// `/users/{id}`; header templates read them as `${path.id}`.
+    path_params: Vec<(String, String)>,
+    /// The client's `x-request-id`, or a generated one.
+    request_id: String,
+    client_ip: String,
+    /// Request body bytes allowed for the matched route, and seen so far.
+    body_limit: u64,
+    body_bytes: u64,
+    /// Request body bytes that may be buffered for a replay.
+    buffer_limit: u64,
+    /// When the route's total timeout runs out.
+    deadline: Option<Instant>,
+    /// When the current upstream attempt started.
+    attempt_started: Option<Instant>,
+    /// Status and latency of the current attempt's response. Counted by the
+    /// upstream's circuit breaker once the attempt is over, unless an error
+    /// later in the exchange counted it as a failure instead.
+    response: Option<(u16, Duration)>,
+    /// Rate limit state to report on the response, when the route has one.
+    rate_limit: Option<Decision>,
+    /// Hash of the route's `hash_key` for this request.
+    hash: Option<u64>,
+    /// Upstream subset picked by the route's traffic split.
+    subset: Option<String>,
+    /// Upstream named by a valid sticky cookie on the request.
+    pinned: Option<u64>,
+    /// Copy of the request for the route's mirror, until it is sent.
+    mirror: Option<MirrorCapture>,
+}
+
+impl RequestCtx {
+    fn header_vars(&self) -> HeaderVars<'_> {
+        HeaderVars {
+            client_ip: &self.client_ip,
+            route_id: self.route.as_ref().map(|r| r.id.as_str()).unwrap_or(""),
+            request_id: &self.request_id,
+            path_params: &self.path_params,
+        }
+    }
+
+    /// Ends the previous upstream attempt, if this is a retry. pingora
+    /// replays the buffered request body to the new attempt, so the body is
+    /// counted from the start again.
+    fn start_attempt(&mut self) {
+        if self.upstream.is_some() {
+            self.finish_attempt();
+            self.body_bytes = 0;
+        }
+    }
+
+    fn finish_attempt(&mut self) {
+        let Some(upstream) = self.upstream.take() else {
+            return;
+        };
+        if let Some((status, latency)) = self.response.take() {
+            circuit::record_response(&upstream, status, latency);
+        }
+        upstream.status.finish_request();
+    }
+
+    /// Counts the current attempt as failed, in place of any response it
+    /// got before the error.
+    fn record_failure(&mut self) {
+        self.response = None;
+        if let Some(upstream) = &self.upstream {
+            circuit::record_failure(upstream, self.attempt_elapsed());
+        }
+    }
+
+    /// Counts a request body chunk; false once the body exceeds the limit.
+    fn count_body(&mut self, len: usize) -> bool {
+        self.body_bytes += len as u64;
+        self.body_bytes <= self.body_limit
+    }
+
+    /// Whether the body seen so far fits the buffer a failover may replay.
+    fn body_buffered(&self) -> bool {
+        self.body_bytes <= self.buffer_limit
+    }
+
+    /// Fails once the route's total timeout has run out. Checked as the
+    /// response comes in, so an upstream that trickles a response cannot
+    /// keep the request going past it.
+    fn check_deadline(&self, now: Instant) -> Result<()> {
+        if self.deadline.is_some_and(|deadline| now >= deadline) {
+            warn!(
+                route_id = self.route.as_ref().map(|r| r.id.as_str()).unwrap_or(""),
+                upstream = self.upstream.as_ref().map(|u| u.url.as_str()).unwrap_or(""),
+                "request deadline exceeded while reading the response"
+            );
+            return Error::e_explain(
+                errors::DEADLINE_EXCEEDED,
+                "total timeout expired before the upstream response finished",
+            );
+        }
+        Ok(())
+    }
+
+    fn attempt_elapsed(&self) -> Duration {
+        self.attempt_started
+            .map(|started| started.elapsed())
+            .unwrap_or_default()
+    }
+
+    /// Whether a failover attempt for `reason` may be made: the route allows
+    /// it, budget remains and a not-yet-tried upstream is available.
+    fn can_fail_over(&self, reason: RetryReason) -> bool {
+        let Some(route) = &self.route else {
+            return false;
+        };
+        route
+            .failover
+            .as_ref()
+            .is_some_and(|failover| failover.allows(reason, self.failovers))
+            && router::select_upstream_excluding(route, self.subset.as_deref(), &self.tried)
+                .is_some()
+    }
+
+    fn fail_over(&mut self, e: &mut Error, reason: RetryReason) {
+        self.failovers += 1;
+        e.set_retry(true);
+        info!(
+            route_id = self.route.as_ref().map(|r| r.id.as_str()).unwrap_or(""),
+            upstream = self.upstream.as_ref().map(|u| u.url.as_str()).unwrap_or(""),
+            reason = ?reason,
+            failovers = self.failovers,
+            "failing over to another upstream"
+        );
+    }
 }
 
 impl GatewayProxy {
-    pub fn new(state: Arc<State>) -> Self {
-        Self { state }
+    pub fn new(
+        state: Arc<State>,
+        error_pages: ErrorPages,
+        max_body_bytes: u64,
+        pre_upstream_body_bytes: u64,
+        cookie_signer: CookieSigner,
+    ) -> Self {
+        Self {
+            state,
+            error_pages,
+            max_body_bytes,
+            pre_upstream_body_bytes,
+            cookie_signer,
+            mirrors: MirrorSender::new(),
+        }
     }
 }
 
+/// The request body can be sent again when it was empty or is still fully
+/// held in the retry buffer, within `limits.pre_upstream_body_bytes`.
+fn body_replayable(session: &mut Session, ctx: &RequestCtx) -> bool {
+    session.as_mut().is_body_empty()
+        || (!session.as_ref().retry_buffer_truncated() && ctx.body_buffered())
+}
+
 #[async_trait]
 impl ProxyHttp for GatewayProxy {
-    type CTX = ();
+    type CTX = RequestCtx;
 
     fn new_ctx(&self) -> Self::CTX {
-        ()
+        RequestCtx::default()
     }
 
-    async fn request_filter(&self, _session: &mut Session, _ctx: &mut Self::CTX) -> Result<bool> {
+    async fn request_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<bool> {
+        let started = Instant::now();
+        ctx.request_id = session
+            .req_header()
+            .headers
+            .get("x-request-id")
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LEN)
+            .map(ToString::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        ctx.client_ip = session
+            .client_addr()
+            .and_then(|addr| addr.as_inet())
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_default();
+
+        let request = session.req_header();
+        let path = request.uri.path();
+        let snapshot = self.state.snapshot();
+        let Some(route) = router::match_route(&snapshot, request).cloned() else {
+            warn!(
+                path = %path,
+                method = %request.method,
+                host = request_host(request).unwrap_or(""),
+                routes = snapshot.routes.len(),
+                "no route match"
+            );
+            self.error_pages
+                .send(session, &errors::ROUTE_NOT_FOUND, &ctx.request_id, &[])
+                .await?;
+            return Ok(true);
+        };
+        ctx.path_params = route
+            .path
+            .as_ref()
+            .map(|matcher| matcher.captures(path))
+            .unwrap_or_default();
+        ctx.body_limit = route.max_body_bytes.unwrap_or(self.max_body_bytes);
+        ctx.buffer_limit = self.pre_upstream_body_bytes;
+        ctx.deadline = route.timeouts.total.map(|total| started + total);
+        ctx.hash = route
+            .hash_key
+            .as_ref()
+            .and_then(|key| key.hash(request, &ctx.client_ip));
+        ctx.subset = route
+            .split
+            .as_ref()
+            .map(|split| split.subset_for(request, &ctx.client_ip).to_string());
+        ctx.pinned = route
+            .sticky
+            .as_ref()
+            .and_then(|sticky| self.cookie_signer.pinned(&route, sticky, request));
+        ctx.mirror = route
+            .mirror
+            .as_ref()
+            .filter(|mirror| mirror.samples(Uuid::new_v4().as_u128() as u64))
+            .map(|mirror| MirrorCapture::new(&route.id, mirror, ctx.buffer_limit));
+        ctx.rate_limit = route.rate_limit.as_ref().map(|limit| {
+            let key = rate_limit_key(&limit.key, request, &ctx.client_ip);
+            self.state.rate_limits().check(&route.id, key, limit)
+        });
+        ctx.route = Some(route);
+
+        if let Some(decision) = ctx.rate_limit.filter(|decision| !decision.allowed) {
+            debug!(
+                request_id = %ctx.request_id,
+                route_id = ctx.route.as_ref().map(|r| r.id.as_str()).unwrap_or(""),
+                retry_after_ms = decision.retry_after.as_millis() as u64,
+                "rate limited"
+            );
+            self.error_pages
+                .send(
+                    session,
+                    &errors::TOO_MANY_REQUESTS,
+                    &ctx.request_id,
+                    &decision.headers(),
+                )
+                .await?;
+            return Ok(true);
+        }
+
+        // Reject up front when the declared length is already too large;
+        // chunked bodies are counted in `request_body_filter`.
+        let declared = session
+            .req_header()
+            .headers
+            .get("content-length")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok());
+        if declared.is_some_and(|length| length > ctx.body_limit) {
+            debug!(
+                request_id = %ctx.request_id,
+                content_length = declared,
+                limit = ctx.body_limit,
+                "request body over limit"
+            );
+            self.error_pages
+                .send(session, &errors::PAYLOAD_TOO_LARGE, &ctx.request_id, &[])
+                .await?;
+            return Ok(true);
+        }
         Ok(false)
     }
 
+    async fn request_body_filter(
+        &self,
+        _session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        if let Some(chunk) = body {
+            if !ctx.count_body(chunk.len()) {
+                return Error::e_explain(
+                    errors::BODY_TOO_LARGE,
+                    format!("request body exceeds {} bytes", ctx.body_limit),
+                );
+            }
+        }
+
+        if let Some(capture) = &mut ctx.mirror {
+            if let Some(chunk) = body {
+                capture.push_body(chunk);
+            }
+        }
+        // The whole request has been seen: hand the copy off without
+        // waiting for it.
+        if end_of_stream {
+            if let Some(capture) = ctx.mirror.take() {
+                self.mirrors.send(capture);
+            }
+        }
+        Ok(())
+    }
+
     async fn upstream_peer(
         &self,
         session: &mut Session,
-        _ctx: &mut Self::CTX,
+        ctx: &mut Self::CTX,
     ) -> Result<Box<HttpPeer>> {
+        ctx.start_attempt();
         let request = session.req_header();
-        let path = request.uri.path().to_string();
-        let method = request.method.as_str().to_string();
-        let host = request
-            .headers
-            .get("host")
-            .and_then(|value| value.to_str().ok())
-            .map(ToString::to_string);
-        let snapshot = self.state.snapshot();
+        let Some(route) = &ctx.route else {
+            return Error::e_explain(ErrorType::InternalError, "no route resolved for request");
+        };
 
-        let route = router::match_route(&snapshot, &path, &method, host.as_deref())
-            .cloned()
+        let exclude: &[String] = if route.failover.is_some() {
+            &ctx.tried
+        } else {
+            &[]
+        };
+        let subset = ctx.subset.as_deref();
+        let upstream = ctx
+            .pinned
+            .and_then(|id| sticky::pinned_upstream(route, subset, exclude, id))
+            .or_else(|| router::select_upstream_hashed(route, subset, exclude, ctx.hash))
             .ok_or_else(|| {
-            warn!(path = %path, method = %method, host = host.as_deref().unwrap_or(""), routes = snapshot.routes.len(), "no route match");
-            Error::new(ErrorType::Custom("no route"))
-        })?;
-        let upstream = router::select_upstream(&route).ok_or_else(|| {
-            warn!(route_id = %route.id, "no upstream available for route");
-            Error::new(ErrorType::Custom("no upstream"))
-        })?;
-
-        let peer = build_peer(&upstream)?;
+                warn!(route_id = %route.id, tried = ctx.tried.len(), "no upstream available for route");
+                Error::new(errors::NO_HEALTHY_UPSTREAM)
+            })?;
+
+        let remaining = match ctx.deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => Some(remaining),
+                _ => {
+                    warn!(route_id = %route.id, attempts = ctx.tried.len(), "request deadline exceeded");
+                    return Error::e_explain(
+                        errors::DEADLINE_EXCEEDED,
+                        "total timeout expired before an upstream responded",
+                    );
+                }
+            },
+            None => None,
+        };
+
+        let mut peer = build_peer(&upstream, &route.timeouts)?;
+        if let Some(per_try) = route.failover.as_ref().and_then(|f| f.per_try_timeout) {
+            cap_per_try(&mut peer, per_try);
+        }
+        if let Some(remaining) = remaining {
+            cap_timeouts(&mut peer, remaining);
+        }
         debug!(
-            path = %path,
-            method = %method,
-            host = host.as_deref().unwrap_or(""),
+            path = %request.uri.path(),
+            method = %request.method,
+            host = request_host(request).unwrap_or(""),
             route_id = %route.id,
             upstream = %upstream.url,
+            attempt = ctx.tried.len() + 1,
             "proxying request"
         );
+        upstream.status.circuit().on_attempt();
+        upstream.status.start_request();
+        ctx.attempt_started = Some(Instant::now());
+        ctx.tried.push(upstream.url.clone());
+        ctx.upstream = Some(upstream);
+        ctx.retry_5xx = false;
         Ok(Box::new(peer))
     }
 
+    async fn upstream_request_filter(
+        &self,
+        _session: &mut Session,
+        upstream_request: &mut RequestHeader,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        let Some(route) = &ctx.route else {
+            return Ok(());
+        };
+
+        if let Some(rewrite) = &route.rewrite {
+            if let Some(path) = rewrite.apply_path(upstream_request.uri.path()) {
+                let uri = rewritten_uri(&path, upstream_request.uri.query());
+                let uri = uri.parse().or_else(|err| {
+                    Error::e_explain(
+                        ErrorType::InternalError,
+                        format!("rewritten path {uri:?} is not a valid URI: {err}"),
+                    )
+                })?;
+                upstream_request.set_uri(uri);
+            }
+            if let Some(host) = &rewrite.host {
+                upstream_request.insert_header("host", host)?;
+            }
+        }
+        route
+            .request_headers
+            .apply(upstream_request, &ctx.header_vars())?;
+
+        if let Some(capture) = &mut ctx.mirror {
+            capture.start(upstream_request);
+        }
+        Ok(())
+    }
+
+    async fn upstream_response_filter(
+        &self,
+        session: &mut Session,
+        upstream_response: &mut ResponseHeader,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        let status = upstream_response.status.as_u16();
+        if let Some(upstream) = &ctx.upstream {
+            outlier::record_status(upstream, status);
+            upstream.status.record_rtt(ctx.attempt_elapsed());
+        }
+        ctx.response = Some((status, ctx.attempt_elapsed()));
+        ctx.check_deadline(Instant::now())?;
+
+        // Nothing has been sent downstream yet, so a 5xx can still be
+        // replaced by another attempt. Only do so when one will follow;
+        // otherwise the upstream response is passed through untouched.
+        if status >= 500 && ctx.can_fail_over(RetryReason::Http5xx) && body_replayable(session, ctx)
+        {
+            ctx.retry_5xx = true;
+            return Error::e_explain(ErrorType::HTTPStatus(status), "upstream responded with 5xx");
+        }
+        Ok(())
+    }
+
+    fn upstream_response_body_filter(
+        &self,
+        _session: &mut Session,
+        _body: &mut Option<Bytes>,
+        _end_of_stream: bool,
+        ctx: &mut Self::CTX,
+    ) -> Result<Option<Duration>> {
+        ctx.check_deadline(Instant::now())?;
+        Ok(None)
+    }
+
     async fn response_filter(
         &self,
         _session: &mut Session,
-        _resp: &mut ResponseHeader,
-        _ctx: &mut Self::CTX,
+        resp: &mut ResponseHeader,
+        ctx: &mut Self::CTX,
     ) -> Result<()> {
+        if let Some(decision) = &ctx.rate_limit {
+            for (name, value) in decision.headers() {
+                resp.insert_header(name, value)?;
+            }
+        }
+        let Some(route) = &ctx.route else {
+            return Ok(());
+        };
+        route.response_headers.apply(resp, &ctx.header_vars())?;
+
+        // Pin the client unless its cookie already names this upstream.
+        if let (Some(sticky), Some(upstream)) = (&route.sticky, &ctx.upstream) {
+            if ctx.pinned != Some(sticky::upstream_id(&upstream.url)) {
+                let value = self.cookie_signer.cookie_value(&route.id, &upstream.url);
+                resp.append_header("set-cookie", sticky.set_cookie(&value))?;
+            }
+        }
         Ok(())
     }
+
+    fn fail_to_connect(
+        &self,
+        _session: &mut Session,
+        _peer: &HttpPeer,
+        ctx: &mut Self::CTX,
+        mut e: Box<Error>,
+    ) -> Box<Error> {
+        if let Some(upstream) = &ctx.upstream {
+            outlier::record_connect_failure(upstream);
+        }
+        ctx.record_failure();
+        // Nothing reached the upstream, so the request is always replayable.
+        if ctx.can_fail_over(RetryReason::ConnectFailure) {
+            ctx.fail_over(&mut e, RetryReason::ConnectFailure);
+        }
+        e
+    }
+
+    fn error_while_proxy(
+        &self,
+        peer: &HttpPeer,
+        session: &mut Session,
+        e: Box<Error>,
+        ctx: &mut Self::CTX,
+        client_reused: bool,
+    ) -> Box<Error> {
+        let mut e = e.more_context(format!("Peer: {peer}"));
+        let reason = match e.etype() {
+            ErrorType::HTTPStatus(_) if ctx.retry_5xx => Some(RetryReason::Http5xx),
+            ErrorType::ReadTimedout | ErrorType::WriteTimedout => Some(RetryReason::Timeout),
+            _ => None,
+        };
+        if circuit::is_upstream_failure(&e) || e.etype() == &errors::DEADLINE_EXCEEDED {
+            ctx.record_failure();
+        }
+
+        match reason {
+            Some(RetryReason::Http5xx) => ctx.fail_over(&mut e, RetryReason::Http5xx),
+            Some(reason) if ctx.can_fail_over(reason) && body_replayable(session, ctx) => {
+                ctx.fail_over(&mut e, reason)
+            }
+            _ => {
+                // Default pingora behavior: only retry stale reused connections.
+                e.retry
+                    .decide_reuse(client_reused && body_replayable(session, ctx));
+            }
+        }
+        e
+    }
+
+    async fn fail_to_proxy(
+        &self,
+        session: &mut Session,
+        e: &Error,
+        ctx: &mut Self::CTX,
+    ) -> FailToProxy {
+        let Some(response) = LocalResponse::for_error(e) else {
+            return FailToProxy {
+                error_code: 0,
+                can_reuse_downstream: false,
+            };
+        };
+        warn!(
+            route_id = ctx.route.as_ref().map(|r| r.id.as_str()).unwrap_or(""),
+            request_id = %ctx.request_id,
+            status = response.status,
+            code = response.code,
+            error = %e,
+            "proxy failed, sending local response"
+        );
+        if let Err(err) = self
+            .error_pages
+            .send(session, &response, &ctx.request_id, &[])
+            .await
+        {
+            warn!(error = %err, "failed to send error response downstream");
+        }
+        FailToProxy {
+            error_code: response.status,
+            can_reuse_downstream: false,
+        }
+    }
+
+    async fn logging(&self, _session: &mut Session, _e: Option<&Error>, ctx: &mut Self::CTX) {
+        ctx.finish_attempt();
+    }
 }
 
-fn build_peer(upstream: &router::Upstream) -> Result<HttpPeer> {
+/// The bucket key for a request. A missing or non-UTF-8 header falls back
+/// to the client IP so anonymous clients do not share one bucket.
+fn rate_limit_key<'a>(
+    key: &'a RateLimitKey,
+    request: &'a RequestHeader,
+    client_ip: &'a str,
+) -> &'a str {
+    match key {
+        RateLimitKey::ClientIp => client_ip,
+        RateLimitKey::Header(name) => request
+            .headers
+            .get(name.as_str())
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or(client_ip),
+        RateLimitKey::Route => "",
+    }
+}
+
+fn request_host(request: &RequestHeader) -> Option<&str> {
+    request
+        .headers
+        .get("host")
+        .and_then(|value| value.to_str().ok())
+}
+
+/// Joins a rewritten path with the original query. A rewrite may add its own
+/// query (`/orders?user=$1`), in which case the original one is appended.
+fn rewritten_uri(path: &str, query: Option<&str>) -> String {
+    match query {
+        Some(query) if !query.is_empty() => {
+            let separator = if path.contains('?') { '&' } else { '?' };
+            format!("{path}{separator}{query}")
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Shortens every attempt timeout to the time left before the deadline, so
+/// a slow attempt surfaces as an upstream timeout rather than overrunning.
+fn cap_timeouts(peer: &mut HttpPeer, remaining: Duration) {
+    let options = &mut peer.options;
+    for timeout in [
+        &mut options.total_connection_timeout,
+        &mut options.read_timeout,
+        &mut options.write_timeout,
+    ] {
+        cap(timeout, remaining);
+    }
+}
+
+/// Bounds connecting and reading by the failover per-try timeout; shorter
+/// route timeouts are kept.
+fn cap_per_try(peer: &mut HttpPeer, per_try: Duration) {
+    let options = &mut peer.options;
+    for timeout in [
+        &mut options.total_connection_timeout,
+        &mut options.read_timeout,
+    ] {
+        cap(timeout, per_try);
+    }
+}
+
+fn cap(timeout: &mut Option<Duration>, limit: Duration) {
+    *timeout = Some(timeout.map_or(limit, |current| current.min(limit)));
+}
+
+pub fn build_peer(upstream: &router::Upstream, timeouts: &Timeouts) -> Result<HttpPeer> {
     let url = if upstream.url.contains("://") {
         Url::parse(&upstream.url)
             .map_err(|_| Error::new(ErrorType::Custom("invalid upstream url")))?
@@ -94,5 +673,197 @@ fn build_peer(upstream: &router::Upstream) -> Result<HttpPeer> {
         .ok_or_else(|| Error::new(ErrorType::Custom("invalid upstream port")))?;
     let addr = format!("{host}:{port}");
 
-    Ok(HttpPeer::new(addr, tls, host.to_string()))
+    let overrides = upstream.tls.as_ref();
+    let sni = overrides
+        .and_then(|o| o.server_name.clone())
+        .unwrap_or_else(|| host.to_string());
+
+    let mut peer = HttpPeer::new(addr, tls, sni);
+    if let Some(overrides) = overrides {
+        if overrides.insecure_skip_verify {
+            peer.options.verify_cert = false;
+            peer.options.verify_hostname = false;
+        }
+        peer.options.ca = overrides.ca.clone();
+    }
+    peer.options.total_connection_timeout = timeouts.connect;
+    peer.options.read_timeout = timeouts.read;
+    peer.options.write_timeout = timeouts.write;
+    peer.options.idle_timeout = timeouts.idle;
+    Ok(peer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::CircuitState;
+    use crate::router::{CircuitBreaker, UpstreamTls};
+
+    #[test]
+    fn peer_uses_url_host_by_default() {
+        let peer = build_peer(
+            &router::Upstream::new("https://10.0.0.5:8443".to_string()),
+            &Timeouts::default(),
+        )
+        .unwrap();
+
+        assert_eq!(peer.sni, "10.0.0.5");
+        assert!(peer.options.verify_cert);
+        assert!(peer.options.verify_hostname);
+    }
+
+    #[test]
+    fn tls_override_sets_sni_and_verification() {
+        let upstream = router::Upstream {
+            tls: Some(UpstreamTls {
+                server_name: Some("payments.svc.corp".to_string()),
+                ca: None,
+                insecure_skip_verify: true,
+            }),
+            ..router::Upstream::new("https://10.0.0.7:8443".to_string())
+        };
+        let peer = build_peer(&upstream, &Timeouts::default()).unwrap();
+
+        assert_eq!(peer.sni, "payments.svc.corp");
+        assert!(!peer.options.verify_cert);
+        assert!(!peer.options.verify_hostname);
+    }
+
+    #[test]
+    fn rewritten_uri_keeps_original_query() {
+        assert_eq!(rewritten_uri("/orders", None), "/orders");
+        assert_eq!(rewritten_uri("/orders", Some("page=2")), "/orders?page=2");
+        assert_eq!(
+            rewritten_uri("/orders?user=7", Some("page=2")),
+            "/orders?user=7&page=2"
+        );
+    }
+
+    #[test]
+    fn route_timeouts_map_to_peer_options() {
+        let timeouts = Timeouts {
+            connect: Some(Duration::from_millis(200)),
+            read: Some(Duration::from_secs(5)),
+            write: None,
+            idle: Some(Duration::from_secs(60)),
+            total: Some(Duration::from_secs(10)),
+        };
+        let mut peer = build_peer(
+            &router::Upstream::new("http://10.0.0.5:8080".to_string()),
+            &timeouts,
+        )
+        .unwrap();
+
+        assert_eq!(
+            peer.options.total_connection_timeout,
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(peer.options.read_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(peer.options.idle_timeout, Some(Duration::from_secs(60)));
+
+        cap_timeouts(&mut peer, Duration::from_secs(1));
+        assert_eq!(
+            peer.options.total_connection_timeout,
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(peer.options.read_timeout, Some(Duration::from_secs(1)));
+        assert_eq!(peer.options.write_timeout, Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn per_try_timeout_keeps_shorter_route_timeouts() {
+        let timeouts = Timeouts {
+            connect: Some(Duration::from_millis(200)),
+            ..Timeouts::default()
+        };
+        let mut peer = build_peer(
+            &router::Upstream::new("http://10.0.0.5:8080".to_string()),
+            &timeouts,
+        )
+        .unwrap();
+
+        cap_per_try(&mut peer, Duration::from_secs(5));
+        assert_eq!(
+            peer.options.total_connection_timeout,
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(peer.options.read_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(peer.options.write_timeout, None);
+    }
+
+    #[test]
+    fn deadline_is_enforced_while_the_response_streams() {
+        let start = Instant::now();
+        let ctx = RequestCtx {
+            deadline: Some(start + Duration::from_secs(1)),
+            ..RequestCtx::default()
+        };
+
+        assert!(ctx.check_deadline(start).is_ok());
+        let err = ctx
+            .check_deadline(start + Duration::from_secs(1))
+            .unwrap_err();
+        assert_eq!(err.etype(), &errors::DEADLINE_EXCEEDED);
+        assert!(RequestCtx::default()
+            .check_deadline(start + Duration::from_secs(3600))
+            .is_ok());
+    }
+
+    #[test]
+    fn responses_cut_off_by_the_upstream_trip_its_circuit() {
+        let upstream = router::Upstream {
+            circuit_breaker: Some(CircuitBreaker {
+                window: Duration::from_secs(10),
+                min_requests: 4,
+                error_rate_percent: 50,
+                latency: None,
+                cooldown: Duration::from_secs(5),
+                half_open_probes: 1,
+            }),
+            ..router::Upstream::new("http://10.0.0.5:8080".to_string())
+        };
+        let reset = Error::new(ErrorType::ConnectionClosed).into_up();
+        let mut ctx = RequestCtx::default();
+
+        for _ in 0..4 {
+            ctx.start_attempt();
+            ctx.upstream = Some(upstream.clone());
+            ctx.response = Some((200, Duration::from_millis(10)));
+            assert!(circuit::is_upstream_failure(&reset));
+            ctx.record_failure();
+        }
+        ctx.finish_attempt();
+
+        assert_eq!(upstream.status.circuit().state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn failover_replay_does_not_count_the_body_twice() {
+        let mut ctx = RequestCtx {
+            body_limit: 100,
+            ..RequestCtx::default()
+        };
+        ctx.start_attempt();
+        ctx.upstream = Some(router::Upstream::new("http://10.0.0.1:8080".to_string()));
+        assert!(ctx.count_body(49));
+
+        ctx.start_attempt();
+        ctx.upstream = Some(router::Upstream::new("http://10.0.0.2:8080".to_string()));
+        assert!(ctx.count_body(49));
+        assert!(!ctx.count_body(52));
+    }
+
+    #[test]
+    fn bodies_past_the_pre_upstream_limit_are_not_replayed() {
+        let mut ctx = RequestCtx {
+            body_limit: 100,
+            buffer_limit: 10,
+            ..RequestCtx::default()
+        };
+        assert!(ctx.count_body(10));
+        assert!(ctx.body_buffered());
+
+        assert!(ctx.count_body(1));
+        assert!(!ctx.body_buffered());
+    }
 }
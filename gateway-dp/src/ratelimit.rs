@@ -0,0 +1,311 @@
+use std::collections::hash_map::{Entry, RandomState};
+use std::collections::{HashMap, VecDeque};
+use std::hash::BuildHasher;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const SHARDS: usize = 16;
+/// Buckets kept per shard. At the cap a new key takes the place of a
+/// bucket that has refilled or has not been used for a while, so memory and
+/// the work per request stay bounded however many keys show up.
+const MAX_BUCKETS_PER_SHARD: usize = 4096;
+
+/// Lowest accepted refill rate: one request a day.
+pub const MIN_REQUESTS_PER_SECOND: f64 = 1.0 / 86_400.0;
+
+/// A route's token bucket: `burst` requests at once, refilled at
+/// `requests_per_second`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimit {
+    pub requests_per_second: f64,
+    pub burst: u32,
+    pub key: RateLimitKey,
+}
+
+/// What a bucket is counted per.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RateLimitKey {
+    ClientIp,
+    /// Value of the named header; requests without it count per client IP.
+    Header(String),
+    /// One bucket for the whole route.
+    Route,
+}
+
+/// Outcome of a rate limit check, with the values for the `RateLimit-*`
+/// response headers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Until the bucket is full again.
+    pub reset: Duration,
+    /// Until the next request would be allowed; zero when allowed.
+    pub retry_after: Duration,
+}
+
+impl Decision {
+    /// `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset`, plus
+    /// `Retry-After` for a rejected request.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            ("ratelimit-limit", self.limit.to_string()),
+            ("ratelimit-remaining", self.remaining.to_string()),
+            ("ratelimit-reset", ceil_secs(self.reset).to_string()),
+        ];
+        if !self.allowed {
+            headers.push((
+                "retry-after",
+                ceil_secs(self.retry_after).max(1).to_string(),
+            ));
+        }
+        headers
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    requests_per_second: f64,
+    burst: f64,
+    /// Used since the eviction hand last passed it.
+    referenced: bool,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.requests_per_second).min(self.burst);
+        self.updated = now;
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * self.requests_per_second >= self.burst
+    }
+}
+
+#[derive(Debug, Default)]
+struct Shard {
+    buckets: HashMap<String, Bucket>,
+    /// Every key in `buckets` once, in the order the eviction hand visits.
+    queue: VecDeque<String>,
+}
+
+impl Shard {
+    /// Drops one bucket, clock style: a refilled bucket goes at once, one
+    /// used since the hand last passed gets a second chance. Keys seen only
+    /// once, as in a flood of distinct keys, are the first to go.
+    fn evict_one(&mut self, now: Instant) {
+        while let Some(key) = self.queue.pop_front() {
+            let Some(bucket) = self.buckets.get_mut(&key) else {
+                continue;
+            };
+            if bucket.referenced && !bucket.is_full(now) {
+                bucket.referenced = false;
+                self.queue.push_back(key);
+            } else {
+                self.buckets.remove(&key);
+                return;
+            }
+        }
+    }
+}
+
+/// Token buckets for every route and key. Lives in [`State`](crate::state::State)
+/// rather than the route snapshot, so config swaps keep the counts.
+#[derive(Debug)]
+pub struct RateLimiter {
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a token for `key` on `route_id` if one is available.
+    pub fn check(&self, route_id: &str, key: &str, limit: &RateLimit) -> Decision {
+        self.check_at(route_id, key, limit, Instant::now())
+    }
+
+    fn check_at(&self, route_id: &str, key: &str, limit: &RateLimit, now: Instant) -> Decision {
+        let bucket_key = format!("{route_id}\u{0}{key}");
+        let shard = &self.shards[self.hasher.hash_one(&bucket_key) as usize % SHARDS];
+        let mut shard = shard
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if shard.buckets.len() >= MAX_BUCKETS_PER_SHARD && !shard.buckets.contains_key(&bucket_key)
+        {
+            shard.evict_one(now);
+        }
+
+        let burst = f64::from(limit.burst.max(1));
+        let rate = limit.requests_per_second;
+        let Shard { buckets, queue } = &mut *shard;
+        let bucket = match buckets.entry(bucket_key) {
+            Entry::Occupied(entry) => {
+                let bucket = entry.into_mut();
+                bucket.referenced = true;
+                bucket
+            }
+            Entry::Vacant(entry) => {
+                queue.push_back(entry.key().clone());
+                entry.insert(Bucket {
+                    tokens: burst,
+                    updated: now,
+                    requests_per_second: rate,
+                    burst,
+                    referenced: false,
+                })
+            }
+        };
+        // Pick up config changes for an existing bucket.
+        bucket.requests_per_second = rate;
+        bucket.burst = burst;
+        bucket.refill(now);
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        let seconds_for = |tokens: f64| {
+            Duration::try_from_secs_f64((tokens / rate).max(0.0)).unwrap_or(Duration::MAX)
+        };
+        Decision {
+            allowed,
+            limit: limit.burst,
+            remaining: bucket.tokens.floor() as u32,
+            reset: seconds_for(burst - bucket.tokens),
+            retry_after: if allowed {
+                Duration::ZERO
+            } else {
+                seconds_for(1.0 - bucket.tokens)
+            },
+        }
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration
+        .as_secs()
+        .saturating_add(u64::from(duration.subsec_nanos() > 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(requests_per_second: f64, burst: u32) -> RateLimit {
+        RateLimit {
+            requests_per_second,
+            burst,
+            key: RateLimitKey::ClientIp,
+        }
+    }
+
+    #[test]
+    fn allows_burst_then_rejects_until_refilled() {
+        let limiter = RateLimiter::new();
+        let limit = limit(2.0, 3);
+        let start = Instant::now();
+
+        for remaining in [2, 1, 0] {
+            let decision = limiter.check_at("orders", "192.0.2.1", &limit, start);
+            assert!(decision.allowed);
+            assert_eq!(decision.remaining, remaining);
+        }
+        let rejected = limiter.check_at("orders", "192.0.2.1", &limit, start);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.retry_after, Duration::from_millis(500));
+        assert_eq!(rejected.reset, Duration::from_millis(1500));
+
+        let later = start + Duration::from_millis(500);
+        assert!(
+            limiter
+                .check_at("orders", "192.0.2.1", &limit, later)
+                .allowed
+        );
+    }
+
+    #[test]
+    fn keys_and_routes_have_separate_buckets() {
+        let limiter = RateLimiter::new();
+        let limit = limit(1.0, 1);
+        let now = Instant::now();
+
+        assert!(limiter.check_at("orders", "a", &limit, now).allowed);
+        assert!(!limiter.check_at("orders", "a", &limit, now).allowed);
+        assert!(limiter.check_at("orders", "b", &limit, now).allowed);
+        assert!(limiter.check_at("users", "a", &limit, now).allowed);
+    }
+
+    #[test]
+    fn rejected_decision_carries_retry_after() {
+        let limiter = RateLimiter::new();
+        let limit = limit(0.5, 1);
+        let now = Instant::now();
+        limiter.check_at("orders", "a", &limit, now);
+
+        let headers = limiter.check_at("orders", "a", &limit, now).headers();
+        assert_eq!(
+            headers,
+            vec![
+                ("ratelimit-limit", "1".to_string()),
+                ("ratelimit-remaining", "0".to_string()),
+                ("ratelimit-reset", "2".to_string()),
+                ("retry-after", "2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tiny_rates_saturate_instead_of_overflowing() {
+        let limiter = RateLimiter::new();
+        let limit = limit(1e-20, 1);
+        let now = Instant::now();
+
+        assert!(limiter.check_at("orders", "a", &limit, now).allowed);
+        let rejected = limiter.check_at("orders", "a", &limit, now);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.retry_after, Duration::MAX);
+        assert!(rejected
+            .headers()
+            .contains(&("retry-after", u64::MAX.to_string())));
+    }
+
+    #[test]
+    fn distinct_keys_cannot_grow_a_shard_past_its_cap() {
+        let limiter = RateLimiter::new();
+        let limit = limit(1.0, 3);
+        let now = Instant::now();
+
+        for idx in 0..SHARDS * MAX_BUCKETS_PER_SHARD * 2 {
+            if idx % 1_000 == 0 {
+                limiter.check_at("orders", "steady", &limit, now);
+            }
+            limiter.check_at("orders", &format!("flood-{idx}"), &limit, now);
+        }
+
+        for shard in &limiter.shards {
+            let shard = shard.lock().unwrap();
+            assert!(shard.buckets.len() <= MAX_BUCKETS_PER_SHARD);
+            assert_eq!(shard.queue.len(), shard.buckets.len());
+        }
+        // The drained bucket of a key in steady use survived the flood.
+        assert!(!limiter.check_at("orders", "steady", &limit, now).allowed);
+    }
+}
@@ -1,51 +1,211 @@
+mod hash;
+mod headers;
+mod host;
+mod index;
 mod matcher;
+mod path;
+mod rewrite;
 mod select;
+mod split;
 
-use std::sync::{atomic::AtomicUsize, Arc};
+use pingora::protocols::tls::CaType;
+use regex::Regex;
+use std::sync::{atomic::AtomicUsize, Arc, Mutex};
+use std::time::Duration;
 
-pub use matcher::match_route;
-pub use select::select_upstream;
+use crate::mirror::Mirror;
+use crate::ratelimit::RateLimit;
+use crate::sticky::StickySession;
+use crate::upstream::UpstreamStatus;
+
+pub use hash::{cookie_value, hash_bytes, HashKey, HashTable};
+pub use headers::{HeaderOps, HeaderTemplate, HeaderVars};
+pub use host::HostMatcher;
+pub use matcher::{match_route, match_route_linear};
+pub use path::{PathMatcher, PathTemplate};
+pub use rewrite::{PathRewrite, Rewrite};
+pub use select::{select_upstream_excluding, select_upstream_hashed, LbPolicy};
+pub use split::{SplitCondition, SplitRule, TrafficSplit};
 
 #[derive(Clone, Debug)]
 pub struct RouteSnapshot {
     pub routes: Vec<Route>,
+    index: index::RouteIndex,
 }
 
 #[derive(Clone, Debug)]
 pub struct Route {
     #[allow(dead_code)]
     pub id: String,
-    pub path_prefix: Option<String>,
+    pub path: Option<PathMatcher>,
     pub methods: Vec<String>,
-    pub host: Option<String>,
+    /// Any one of these must match the request host; empty matches any host.
+    pub hosts: Vec<HostMatcher>,
+    pub headers: Vec<HeaderMatcher>,
+    pub query: Vec<QueryMatcher>,
     pub upstreams: Vec<Upstream>,
+    pub lb: LbPolicy,
+    /// What `ring_hash` and `maglev` hash on.
+    pub hash_key: Option<HashKey>,
+    /// Built for hashing policies; reused across snapshots while the
+    /// upstream set stays the same.
+    pub hash_table: Option<Arc<HashTable>>,
+    /// Pins clients to the upstream their first request went to.
+    pub sticky: Option<StickySession>,
+    pub failover: Option<Failover>,
+    pub rewrite: Option<Rewrite>,
+    /// Applied to the upstream request, after any rewrite.
+    pub request_headers: HeaderOps,
+    /// Applied to the response sent downstream.
+    pub response_headers: HeaderOps,
+    /// Overrides the listener-wide `limits.max_body_bytes`.
+    pub max_body_bytes: Option<u64>,
+    pub timeouts: Timeouts,
+    pub rate_limit: Option<RateLimit>,
+    /// Shadow upstream receiving copies of a share of the requests.
+    pub mirror: Option<Mirror>,
+    /// Sends requests to upstream subsets by header, cookie or hash bucket.
+    pub split: Option<TrafficSplit>,
     pub rr_index: Arc<AtomicUsize>,
+    pub wrr_current: Arc<Mutex<Vec<i64>>>,
+}
+
+/// A request header predicate. Every matcher on a route must hold.
+#[derive(Clone, Debug)]
+pub struct HeaderMatcher {
+    /// Lowercase header name.
+    pub name: String,
+    pub rule: ValueRule,
+    /// Compare values case-insensitively. Exact and prefix operands are
+    /// stored lowercased and regexes compiled case-insensitive when set.
+    pub ignore_case: bool,
+}
+
+/// A query parameter predicate, evaluated against percent-decoded values.
+/// Every matcher on a route must hold.
+#[derive(Clone, Debug)]
+pub struct QueryMatcher {
+    pub name: String,
+    pub rule: ValueRule,
+}
+
+#[derive(Clone, Debug)]
+pub enum ValueRule {
+    Exact(String),
+    Prefix(String),
+    Regex(Regex),
+    Present,
+    Absent,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failover {
+    pub max_failovers: u32,
+    pub retry_on: RetryOn,
+    pub per_try_timeout: Option<Duration>,
+}
+
+/// Upstream timeouts for a route. `total` bounds the whole request, from
+/// the first attempt until response headers arrive, across failovers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    pub connect: Option<Duration>,
+    pub read: Option<Duration>,
+    pub write: Option<Duration>,
+    /// How long a pooled upstream connection may sit unused.
+    pub idle: Option<Duration>,
+    pub total: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetryOn {
+    pub connect_failure: bool,
+    pub http_5xx: bool,
+    pub timeout: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryReason {
+    ConnectFailure,
+    Http5xx,
+    Timeout,
 }
 
 #[derive(Clone, Debug)]
 pub struct Upstream {
     pub url: String,
+    pub weight: u32,
+    pub priority: u32,
+    pub health_check: Option<HealthCheck>,
+    pub outlier_detection: Option<OutlierDetection>,
+    pub circuit_breaker: Option<CircuitBreaker>,
+    pub tls: Option<UpstreamTls>,
+    /// Named group the route's `split` sends traffic to.
+    pub subset: Option<String>,
+    pub status: Arc<UpstreamStatus>,
+}
+
+/// Per-upstream overrides for TLS origination.
+#[derive(Clone, Debug, Default)]
+pub struct UpstreamTls {
+    /// SNI and expected certificate name, instead of the URL host.
+    pub server_name: Option<String>,
+    /// CA bundle that replaces the system trust store.
+    pub ca: Option<Arc<CaType>>,
+    pub insecure_skip_verify: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthCheck {
+    pub path: String,
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub unhealthy_threshold: u32,
+    pub healthy_threshold: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutlierDetection {
+    pub consecutive_5xx: u32,
+    pub eject: Duration,
+}
+
+/// Opens the upstream's circuit when, over `window` and at least
+/// `min_requests`, the failure rate reaches `error_rate_percent` or the mean
+/// latency exceeds `latency`. After `cooldown`, `half_open_probes` requests
+/// are let through and the circuit closes once they all succeed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    pub window: Duration,
+    pub min_requests: u32,
+    /// 0 disables the error rate trigger.
+    pub error_rate_percent: u32,
+    pub latency: Option<Duration>,
+    pub cooldown: Duration,
+    pub half_open_probes: u32,
 }
 
 impl RouteSnapshot {
+    /// Builds the routing index for `routes`; the snapshot is immutable
+    /// afterwards so the index cannot go stale.
+    pub fn new(routes: Vec<Route>) -> Self {
+        let index = index::RouteIndex::build(&routes);
+        Self { routes, index }
+    }
+
     pub fn empty() -> Self {
-        Self { routes: Vec::new() }
+        Self::new(Vec::new())
     }
 
     #[allow(dead_code)]
     pub fn from_static() -> Self {
-        Self {
-            routes: vec![Route {
-                id: "default".to_string(),
-                path_prefix: Some("/".to_string()),
-                methods: vec!["GET".to_string()],
-                host: None,
-                upstreams: vec![Upstream {
-                    url: "http://127.0.0.1:9000".to_string(),
-                }],
-                rr_index: Arc::new(AtomicUsize::new(0)),
-            }],
-        }
+        Self::new(vec![Route::new(
+            "default".to_string(),
+            Some("/".to_string()),
+            vec!["GET".to_string()],
+            None,
+            vec![Upstream::new("http://127.0.0.1:9000".to_string())],
+        )])
     }
 }
 
@@ -57,13 +217,82 @@ impl Route {
         host: Option<String>,
         upstreams: Vec<Upstream>,
     ) -> Self {
+        let wrr_current = vec![0; upstreams.len()];
         Self {
             id,
-            path_prefix,
+            path: path_prefix.map(PathMatcher::Prefix),
             methods,
-            host,
+            hosts: host.iter().map(|host| HostMatcher::parse(host)).collect(),
+            headers: Vec::new(),
+            query: Vec::new(),
             upstreams,
+            lb: LbPolicy::default(),
+            hash_key: None,
+            hash_table: None,
+            sticky: None,
+            failover: None,
+            rewrite: None,
+            request_headers: HeaderOps::default(),
+            response_headers: HeaderOps::default(),
+            max_body_bytes: None,
+            timeouts: Timeouts::default(),
+            rate_limit: None,
+            mirror: None,
+            split: None,
             rr_index: Arc::new(AtomicUsize::new(0)),
+            wrr_current: Arc::new(Mutex::new(wrr_current)),
         }
     }
 }
+
+impl Failover {
+    /// Whether another attempt may follow `failovers` earlier retries that
+    /// failed for `reason`.
+    pub fn allows(&self, reason: RetryReason, failovers: u32) -> bool {
+        let enabled = match reason {
+            RetryReason::ConnectFailure => self.retry_on.connect_failure,
+            RetryReason::Http5xx => self.retry_on.http_5xx,
+            RetryReason::Timeout => self.retry_on.timeout,
+        };
+        enabled && failovers < self.max_failovers
+    }
+}
+
+impl Upstream {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            weight: 1,
+            priority: 0,
+            health_check: None,
+            outlier_detection: None,
+            circuit_breaker: None,
+            tls: None,
+            subset: None,
+            status: Arc::new(UpstreamStatus::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failover_respects_reasons_and_budget() {
+        let failover = Failover {
+            max_failovers: 2,
+            retry_on: RetryOn {
+                connect_failure: true,
+                http_5xx: true,
+                timeout: false,
+            },
+            per_try_timeout: None,
+        };
+
+        assert!(failover.allows(RetryReason::ConnectFailure, 0));
+        assert!(failover.allows(RetryReason::Http5xx, 1));
+        assert!(!failover.allows(RetryReason::Http5xx, 2));
+        assert!(!failover.allows(RetryReason::Timeout, 0));
+    }
+}
@@ -0,0 +1,371 @@
+use pingora::http::RequestHeader;
+use std::fmt;
+
+use super::{LbPolicy, Upstream};
+
+/// Ring points per unit of upstream weight. Fixed rather than normalized,
+/// so adding or removing an upstream leaves every other upstream's points
+/// in place and only the keys next to the changed points move.
+const RING_POINTS_PER_WEIGHT: u64 = 128;
+/// Bound on the ring size. Weights too large for a fixed count per unit
+/// share out this many points in proportion to weight instead.
+const MAX_RING_POINTS: u64 = 1 << 20;
+/// Maglev lookup table size; must be prime.
+const MAGLEV_TABLE_SIZE: usize = 65_537;
+
+/// What `ring_hash` and `maglev` load balancing hash on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HashKey {
+    /// Lowercase header name.
+    Header(String),
+    Cookie(String),
+    ClientIp,
+    /// 0-based index of a non-empty path segment.
+    PathSegment(usize),
+}
+
+impl HashKey {
+    /// The hash of the request's key, or `None` when the request has none.
+    pub fn hash(&self, request: &RequestHeader, client_ip: &str) -> Option<u64> {
+        let value = match self {
+            Self::Header(name) => request.headers.get(name.as_str())?.as_bytes(),
+            Self::Cookie(name) => cookie_value(request, name)?.as_bytes(),
+            Self::ClientIp if client_ip.is_empty() => return None,
+            Self::ClientIp => client_ip.as_bytes(),
+            Self::PathSegment(index) => request
+                .uri
+                .path()
+                .split('/')
+                .filter(|segment| !segment.is_empty())
+                .nth(*index)?
+                .as_bytes(),
+        };
+        Some(hash_bytes(value))
+    }
+}
+
+/// The value of cookie `name` in the request's `Cookie` headers.
+pub fn cookie_value<'a>(request: &'a RequestHeader, name: &str) -> Option<&'a str> {
+    request
+        .headers
+        .get_all("cookie")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// FNV-1a with the murmur3 finalizer. Unlike `std`'s hasher it is stable
+/// across processes, so every data plane maps a key to the same upstream.
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    mix64(hash)
+}
+
+/// The murmur3 64-bit finalizer: spreads every input bit over the output.
+pub fn mix64(mut value: u64) -> u64 {
+    value ^= value >> 33;
+    value = value.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    value ^= value >> 33;
+    value = value.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    value ^ (value >> 33)
+}
+
+/// Lookup table for a hashing policy, mapping hashes to indexes into the
+/// route's upstreams. Built once per upstream set and shared by snapshots
+/// that keep the same upstreams.
+pub struct HashTable {
+    policy: LbPolicy,
+    /// The `(url, weight)` pairs the table was built from, in route order.
+    members: Vec<(String, u32)>,
+    entries: Entries,
+}
+
+enum Entries {
+    /// `(point, upstream)` sorted by point.
+    Ring(Vec<(u64, usize)>),
+    Maglev(Vec<usize>),
+}
+
+impl HashTable {
+    /// Builds the table for `policy`, or `None` when it does not hash.
+    pub fn build(policy: LbPolicy, upstreams: &[Upstream]) -> Option<Self> {
+        let members: Vec<(String, u32)> = upstreams
+            .iter()
+            .map(|upstream| (upstream.url.clone(), upstream.weight))
+            .collect();
+        let entries = match policy {
+            LbPolicy::RingHash => Entries::Ring(build_ring(&members)),
+            LbPolicy::Maglev => Entries::Maglev(build_maglev(&members)),
+            _ => return None,
+        };
+        Some(Self {
+            policy,
+            members,
+            entries,
+        })
+    }
+
+    /// Whether the table was built for `policy` over the same upstream
+    /// URLs and weights, so a new snapshot can keep using it.
+    pub fn built_for(&self, policy: LbPolicy, upstreams: &[Upstream]) -> bool {
+        self.policy == policy
+            && self.members.len() == upstreams.len()
+            && self
+                .members
+                .iter()
+                .zip(upstreams)
+                .all(|((url, weight), upstream)| *url == upstream.url && *weight == upstream.weight)
+    }
+
+    /// The upstream `hash` maps to, or when `usable` rejects it, the next
+    /// one along the table that it accepts.
+    pub fn pick(&self, hash: u64, usable: impl Fn(usize) -> bool) -> Option<usize> {
+        match &self.entries {
+            Entries::Ring(ring) => {
+                let start = ring.partition_point(|(point, _)| *point < hash);
+                ring[start..]
+                    .iter()
+                    .chain(&ring[..start])
+                    .map(|(_, idx)| *idx)
+                    .find(|idx| usable(*idx))
+            }
+            Entries::Maglev(table) => {
+                if table.is_empty() {
+                    return None;
+                }
+                let start = (hash % table.len() as u64) as usize;
+                table[start..]
+                    .iter()
+                    .chain(&table[..start])
+                    .copied()
+                    .find(|idx| usable(*idx))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for HashTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = match &self.entries {
+            Entries::Ring(ring) => ring.len(),
+            Entries::Maglev(table) => table.len(),
+        };
+        f.debug_struct("HashTable")
+            .field("policy", &self.policy)
+            .field("members", &self.members)
+            .field("entries", &entries)
+            .finish()
+    }
+}
+
+/// Consistent hash ring with points proportional to weight; upstreams with
+/// weight 0 get none.
+fn build_ring(members: &[(String, u32)]) -> Vec<(u64, usize)> {
+    let total: u64 = members.iter().map(|(_, weight)| u64::from(*weight)).sum();
+    if total == 0 {
+        return Vec::new();
+    }
+    // Every weighted upstream keeps a point, so only the rest is shared.
+    let weighted = members.iter().filter(|(_, weight)| *weight > 0).count() as u64;
+    let shared = MAX_RING_POINTS.saturating_sub(weighted);
+    let points = |weight: u64| match weight {
+        0 => 0,
+        _ if total.saturating_mul(RING_POINTS_PER_WEIGHT) <= MAX_RING_POINTS => {
+            weight * RING_POINTS_PER_WEIGHT
+        }
+        _ => 1 + weight * shared / total,
+    };
+
+    let mut ring = Vec::new();
+    for (idx, (url, weight)) in members.iter().enumerate() {
+        for replica in 0..points(u64::from(*weight)) {
+            ring.push((hash_bytes(format!("{url}#{replica}").as_bytes()), idx));
+        }
+    }
+    ring.sort_unstable();
+    ring
+}
+
+/// Maglev lookup table (Eisenbud et al., 2016). Each upstream fills slots
+/// along its own permutation of the table, taking turns in proportion to
+/// its weight, so removing one only reassigns the slots it held and a few
+/// others.
+fn build_maglev(members: &[(String, u32)]) -> Vec<usize> {
+    let max_weight = members.iter().map(|(_, weight)| *weight).max().unwrap_or(0);
+    if max_weight == 0 {
+        return Vec::new();
+    }
+
+    let size = MAGLEV_TABLE_SIZE as u64;
+    let permutations: Vec<(u64, u64)> = members
+        .iter()
+        .map(|(url, _)| {
+            let offset = hash_bytes(url.as_bytes()) % size;
+            let skip = hash_bytes(format!("{url}#skip").as_bytes()) % (size - 1) + 1;
+            (offset, skip)
+        })
+        .collect();
+
+    let mut table = vec![usize::MAX; MAGLEV_TABLE_SIZE];
+    let mut next = vec![0u64; members.len()];
+    let mut credit = vec![0u64; members.len()];
+    let mut filled = 0;
+    while filled < MAGLEV_TABLE_SIZE {
+        for (idx, (_, weight)) in members.iter().enumerate() {
+            credit[idx] += u64::from(*weight);
+            if credit[idx] < u64::from(max_weight) {
+                continue;
+            }
+            credit[idx] -= u64::from(max_weight);
+
+            let (offset, skip) = permutations[idx];
+            loop {
+                let slot = ((offset + next[idx] * skip) % size) as usize;
+                next[idx] += 1;
+                if table[slot] == usize::MAX {
+                    table[slot] = idx;
+                    filled += 1;
+                    break;
+                }
+            }
+            if filled == MAGLEV_TABLE_SIZE {
+                break;
+            }
+        }
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upstreams(count: usize) -> Vec<Upstream> {
+        (0..count)
+            .map(|idx| Upstream::new(format!("http://10.0.0.{idx}:8080")))
+            .collect()
+    }
+
+    fn assignments(table: &HashTable, keys: u32) -> Vec<usize> {
+        (0..keys)
+            .map(|key| {
+                table
+                    .pick(hash_bytes(format!("user-{key}").as_bytes()), |_| true)
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    fn shares(assignments: &[usize], upstreams: usize) -> Vec<usize> {
+        let mut counts = vec![0; upstreams];
+        for idx in assignments {
+            counts[*idx] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn reads_keys_from_the_request() {
+        let mut request = RequestHeader::build("GET", b"/tenants/acme/items", None).unwrap();
+        request.insert_header("x-user", "alice").unwrap();
+        request
+            .insert_header("cookie", "theme=dark; session=abc123")
+            .unwrap();
+
+        let hash = |key: HashKey| key.hash(&request, "192.0.2.1");
+        assert_eq!(
+            hash(HashKey::Header("x-user".to_string())),
+            Some(hash_bytes(b"alice"))
+        );
+        assert_eq!(
+            hash(HashKey::Cookie("session".to_string())),
+            Some(hash_bytes(b"abc123"))
+        );
+        assert_eq!(hash(HashKey::ClientIp), Some(hash_bytes(b"192.0.2.1")));
+        assert_eq!(hash(HashKey::PathSegment(1)), Some(hash_bytes(b"acme")));
+        assert_eq!(hash(HashKey::PathSegment(3)), None);
+        assert_eq!(hash(HashKey::Cookie("missing".to_string())), None);
+    }
+
+    #[test]
+    fn policies_spread_keys_by_weight() {
+        let mut upstreams = upstreams(3);
+        upstreams[2].weight = 2;
+
+        for policy in [LbPolicy::RingHash, LbPolicy::Maglev] {
+            let table = HashTable::build(policy, &upstreams).unwrap();
+            let counts = shares(&assignments(&table, 8_000), 3);
+            for (count, expected) in counts.iter().zip([2_000, 2_000, 4_000]) {
+                assert!(
+                    count.abs_diff(expected) < 500,
+                    "{policy:?} shares {counts:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn removing_an_upstream_only_moves_its_keys() {
+        let before = upstreams(5);
+        let after = before[..4].to_vec();
+
+        for policy in [LbPolicy::RingHash, LbPolicy::Maglev] {
+            let old = assignments(&HashTable::build(policy, &before).unwrap(), 5_000);
+            let new = assignments(&HashTable::build(policy, &after).unwrap(), 5_000);
+
+            let moved_elsewhere = old
+                .iter()
+                .zip(&new)
+                .filter(|(old, new)| **old != 4 && old != new)
+                .count();
+            // Ring hash moves none; maglev may reshuffle a few slots.
+            assert!(moved_elsewhere < 250, "{policy:?} moved {moved_elsewhere}");
+        }
+    }
+
+    #[test]
+    fn huge_weights_do_not_grow_the_ring_past_its_bound() {
+        let members: Vec<(String, u32)> = [u32::MAX, u32::MAX / 2, 1]
+            .into_iter()
+            .enumerate()
+            .map(|(idx, weight)| (format!("http://10.0.0.{idx}:8080"), weight))
+            .collect();
+
+        let ring = build_ring(&members);
+        assert!(ring.len() as u64 <= MAX_RING_POINTS, "{}", ring.len());
+        let points = |member| ring.iter().filter(|(_, idx)| *idx == member).count();
+        assert!(points(0).abs_diff(points(1) * 2) < 10);
+        assert_eq!(points(2), 1);
+    }
+
+    #[test]
+    fn unusable_upstreams_are_skipped() {
+        let table = HashTable::build(LbPolicy::RingHash, &upstreams(3)).unwrap();
+        let hash = hash_bytes(b"user-1");
+        let first = table.pick(hash, |_| true).unwrap();
+
+        let fallback = table.pick(hash, |idx| idx != first).unwrap();
+        assert_ne!(fallback, first);
+        assert_eq!(table.pick(hash, |_| false), None);
+    }
+
+    #[test]
+    fn table_is_reusable_only_for_the_same_upstream_set() {
+        let upstreams = upstreams(2);
+        let table = HashTable::build(LbPolicy::Maglev, &upstreams).unwrap();
+
+        assert!(table.built_for(LbPolicy::Maglev, &upstreams));
+        assert!(!table.built_for(LbPolicy::RingHash, &upstreams));
+        let mut reweighted = upstreams.clone();
+        reweighted[0].weight = 3;
+        assert!(!table.built_for(LbPolicy::Maglev, &reweighted));
+        assert!(HashTable::build(LbPolicy::RoundRobin, &upstreams).is_none());
+    }
+}
@@ -0,0 +1,214 @@
+use pingora::http::{RequestHeader, ResponseHeader};
+use pingora::Result;
+
+/// Header changes for one direction of a route: removals first, then `set`
+/// (replacing any existing values), then `add` (appending).
+#[derive(Clone, Debug, Default)]
+pub struct HeaderOps {
+    pub add: Vec<(String, HeaderTemplate)>,
+    pub set: Vec<(String, HeaderTemplate)>,
+    pub remove: Vec<String>,
+}
+
+/// A header value with `${client_ip}`, `${route_id}` or `${request_id}`
+/// placeholders, and `${path.<name>}` for a variable captured by the
+/// route's path template. `$` without a following `{` is kept as is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Var(Var),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Var {
+    ClientIp,
+    RouteId,
+    RequestId,
+    PathParam(String),
+}
+
+/// Values substituted into [`HeaderTemplate`]s for one request.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeaderVars<'a> {
+    pub client_ip: &'a str,
+    pub route_id: &'a str,
+    pub request_id: &'a str,
+    /// Variables captured by the route's path template.
+    pub path_params: &'a [(String, String)],
+}
+
+/// Header maps a [`HeaderOps`] can be applied to.
+pub trait HeaderTarget {
+    fn remove(&mut self, name: &str);
+    fn set(&mut self, name: String, value: String) -> Result<()>;
+    fn add(&mut self, name: String, value: String) -> Result<()>;
+}
+
+impl HeaderOps {
+    pub fn apply(&self, target: &mut impl HeaderTarget, vars: &HeaderVars<'_>) -> Result<()> {
+        for name in &self.remove {
+            target.remove(name);
+        }
+        for (name, value) in &self.set {
+            target.set(name.clone(), value.render(vars))?;
+        }
+        for (name, value) in &self.add {
+            target.add(name.clone(), value.render(vars))?;
+        }
+        Ok(())
+    }
+}
+
+impl HeaderTemplate {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            literal.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unterminated variable in {value:?}"))?;
+            let var = match &after[..end] {
+                "client_ip" => Var::ClientIp,
+                "route_id" => Var::RouteId,
+                "request_id" => Var::RequestId,
+                other => match other.strip_prefix("path.") {
+                    Some(name) if !name.is_empty() => Var::PathParam(name.to_string()),
+                    _ => return Err(format!("unknown variable ${{{other}}}")),
+                },
+            };
+            if !literal.is_empty() {
+                parts.push(Part::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(Part::Var(var));
+            rest = &after[end + 1..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    pub fn render(&self, vars: &HeaderVars<'_>) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            out.push_str(match part {
+                Part::Literal(text) => text,
+                Part::Var(Var::ClientIp) => vars.client_ip,
+                Part::Var(Var::RouteId) => vars.route_id,
+                Part::Var(Var::RequestId) => vars.request_id,
+                Part::Var(Var::PathParam(name)) => vars
+                    .path_params
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map_or("", |(_, value)| value.as_str()),
+            });
+        }
+        out
+    }
+}
+
+impl HeaderTarget for RequestHeader {
+    fn remove(&mut self, name: &str) {
+        self.remove_header(name);
+    }
+
+    fn set(&mut self, name: String, value: String) -> Result<()> {
+        self.insert_header(name, value)
+    }
+
+    fn add(&mut self, name: String, value: String) -> Result<()> {
+        self.append_header(name, value).map(|_| ())
+    }
+}
+
+impl HeaderTarget for ResponseHeader {
+    fn remove(&mut self, name: &str) {
+        self.remove_header(name);
+    }
+
+    fn set(&mut self, name: String, value: String) -> Result<()> {
+        self.insert_header(name, value)
+    }
+
+    fn add(&mut self, name: String, value: String) -> Result<()> {
+        self.append_header(name, value).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARS: HeaderVars<'static> = HeaderVars {
+        client_ip: "192.0.2.10",
+        route_id: "orders",
+        request_id: "req-1",
+        path_params: &[],
+    };
+
+    fn values(request: &RequestHeader, name: &str) -> Vec<String> {
+        request
+            .headers
+            .get_all(name)
+            .iter()
+            .map(|value| value.to_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn renders_variables() {
+        let template =
+            HeaderTemplate::parse("${route_id}/${request_id} from ${client_ip} $5").unwrap();
+        assert_eq!(template.render(&VARS), "orders/req-1 from 192.0.2.10 $5");
+    }
+
+    #[test]
+    fn renders_path_template_variables() {
+        let params = [("id".to_string(), "42".to_string())];
+        let vars = HeaderVars {
+            path_params: &params,
+            ..VARS
+        };
+        let template = HeaderTemplate::parse("user-${path.id}${path.missing}").unwrap();
+        assert_eq!(template.render(&vars), "user-42");
+    }
+
+    #[test]
+    fn rejects_unknown_and_unterminated_variables() {
+        assert!(HeaderTemplate::parse("${user}").is_err());
+        assert!(HeaderTemplate::parse("${path.}").is_err());
+        assert!(HeaderTemplate::parse("${route_id").is_err());
+    }
+
+    #[test]
+    fn applies_remove_then_set_then_add() {
+        let template = |value| HeaderTemplate::parse(value).unwrap();
+        let ops = HeaderOps {
+            add: vec![("x-trace".to_string(), template("${request_id}"))],
+            set: vec![
+                ("x-route".to_string(), template("${route_id}")),
+                ("x-trace".to_string(), template("gateway")),
+            ],
+            remove: vec!["x-internal".to_string()],
+        };
+        let mut request = RequestHeader::build("GET", b"/", None).unwrap();
+        request.insert_header("x-internal", "secret").unwrap();
+        request.insert_header("x-route", "spoofed").unwrap();
+        request.insert_header("x-trace", "client").unwrap();
+
+        ops.apply(&mut request, &VARS).unwrap();
+
+        assert!(values(&request, "x-internal").is_empty());
+        assert_eq!(values(&request, "x-route"), vec!["orders"]);
+        assert_eq!(values(&request, "x-trace"), vec!["gateway", "req-1"]);
+    }
+}
@@ -0,0 +1,102 @@
+use super::matcher::normalize_host;
+
+/// A route host pattern, stored normalized (lowercase, no port).
+///
+/// - `api.example.com` matches that host only.
+/// - `*.example.com` matches any subdomain, but not `example.com` itself.
+/// - `.example.com` matches `example.com` and any subdomain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HostMatcher {
+    Exact(String),
+    Wildcard { domain: String, include_apex: bool },
+}
+
+impl HostMatcher {
+    pub fn parse(pattern: &str) -> Self {
+        let pattern = normalize_host(pattern);
+        if let Some(domain) = pattern.strip_prefix("*.") {
+            Self::Wildcard {
+                domain: domain.to_string(),
+                include_apex: false,
+            }
+        } else if let Some(domain) = pattern.strip_prefix('.') {
+            Self::Wildcard {
+                domain: domain.to_string(),
+                include_apex: true,
+            }
+        } else {
+            Self::Exact(pattern)
+        }
+    }
+
+    /// Specificity of this pattern for an already normalized `host`, or
+    /// `None` when it does not match. Exact hosts rank above wildcards, and
+    /// wildcards with a longer domain above shorter ones.
+    pub fn rank(&self, host: &str) -> Option<(u8, usize)> {
+        match self {
+            Self::Exact(expected) => (expected == host).then_some((2, expected.len())),
+            Self::Wildcard {
+                domain,
+                include_apex,
+            } => {
+                let subdomain = host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|label| label.len() > 1 && label.ends_with('.'));
+                let apex = *include_apex && host == domain;
+                (subdomain || apex).then_some((1, domain.len()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pattern_kinds() {
+        assert_eq!(
+            HostMatcher::parse("API.example.com:443"),
+            HostMatcher::Exact("api.example.com".to_string())
+        );
+        assert_eq!(
+            HostMatcher::parse("*.Example.com"),
+            HostMatcher::Wildcard {
+                domain: "example.com".to_string(),
+                include_apex: false
+            }
+        );
+        assert_eq!(
+            HostMatcher::parse(".example.com"),
+            HostMatcher::Wildcard {
+                domain: "example.com".to_string(),
+                include_apex: true
+            }
+        );
+    }
+
+    #[test]
+    fn wildcard_and_suffix_semantics() {
+        let wildcard = HostMatcher::parse("*.example.com");
+        assert!(wildcard.rank("acme.example.com").is_some());
+        assert!(wildcard.rank("eu.acme.example.com").is_some());
+        assert!(wildcard.rank("example.com").is_none());
+        assert!(wildcard.rank("badexample.com").is_none());
+
+        let suffix = HostMatcher::parse(".example.com");
+        assert!(suffix.rank("example.com").is_some());
+        assert!(suffix.rank("acme.example.com").is_some());
+        assert!(suffix.rank("example.org").is_none());
+    }
+
+    #[test]
+    fn exact_outranks_longer_and_shorter_wildcards() {
+        let host = "a.eu.example.com";
+        let exact = HostMatcher::parse(host).rank(host).unwrap();
+        let long = HostMatcher::parse("*.eu.example.com").rank(host).unwrap();
+        let short = HostMatcher::parse("*.example.com").rank(host).unwrap();
+
+        assert!(exact > long);
+        assert!(long > short);
+    }
+}
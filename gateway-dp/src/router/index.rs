@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use super::matcher::normalize_host;
+use super::{HostMatcher, PathMatcher, Route};
+
+/// Lookup structure built once per [`RouteSnapshot`](super::RouteSnapshot).
+///
+/// Routes are bucketed by normalized host, with wildcard and suffix patterns
+/// keyed by their domain and looked up by walking the request host's labels
+/// (routes without a host share one bucket), then by path rule: exact paths
+/// in a map, prefixes in a radix tree, and regex, template and path-less
+/// routes in a short list that is checked one by one. A lookup only yields
+/// candidates whose host and, for indexed kinds, path already match; method,
+/// header and query rules are left to the matcher.
+#[derive(Clone, Debug, Default)]
+pub struct RouteIndex {
+    hosts: HashMap<String, PathIndex>,
+    wildcards: HashMap<String, PathIndex>,
+    any_host: PathIndex,
+}
+
+/// A route position in the snapshot, and whether its path still has to be
+/// checked against the request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub route: usize,
+    pub check_path: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+struct PathIndex {
+    exact: HashMap<String, Vec<usize>>,
+    prefixes: RadixNode,
+    scan: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct RadixNode {
+    /// Edge labels of siblings never share a first byte.
+    children: Vec<(String, RadixNode)>,
+    /// Routes whose prefix ends exactly at this node.
+    routes: Vec<usize>,
+}
+
+impl RouteIndex {
+    pub fn build(routes: &[Route]) -> Self {
+        let mut index = Self::default();
+        for (position, route) in routes.iter().enumerate() {
+            if route.hosts.is_empty() {
+                index.any_host.insert(position, route.path.as_ref());
+            }
+            for host in &route.hosts {
+                match host {
+                    HostMatcher::Exact(host) => index.hosts.entry(host.clone()),
+                    HostMatcher::Wildcard {
+                        domain,
+                        include_apex,
+                    } => {
+                        if *include_apex {
+                            index
+                                .hosts
+                                .entry(domain.clone())
+                                .or_default()
+                                .insert(position, route.path.as_ref());
+                        }
+                        index.wildcards.entry(domain.clone())
+                    }
+                }
+                .or_default()
+                .insert(position, route.path.as_ref());
+            }
+        }
+        index
+    }
+
+    /// Candidates for a request, in snapshot order so ties resolve exactly
+    /// as a linear scan would. A route listing several matching hosts is
+    /// yielded once.
+    pub fn candidates(&self, path: &str, host: Option<&str>) -> Vec<Candidate> {
+        let mut out = Vec::new();
+        if let Some(host) = host.map(normalize_host) {
+            if let Some(bucket) = self.hosts.get(&host) {
+                bucket.collect(path, &mut out);
+            }
+            for (dot, _) in host.match_indices('.') {
+                if let Some(bucket) = self.wildcards.get(&host[dot + 1..]) {
+                    bucket.collect(path, &mut out);
+                }
+            }
+        }
+        self.any_host.collect(path, &mut out);
+        out.sort_unstable_by_key(|candidate| candidate.route);
+        out.dedup_by_key(|candidate| candidate.route);
+        out
+    }
+}
+
+impl PathIndex {
+    fn insert(&mut self, position: usize, path: Option<&PathMatcher>) {
+        match path {
+            Some(PathMatcher::Exact(value)) => {
+                self.exact.entry(value.clone()).or_default().push(position)
+            }
+            Some(PathMatcher::Prefix(prefix)) => self.prefixes.insert(prefix, position),
+            Some(PathMatcher::Regex(_)) | Some(PathMatcher::Template(_)) | None => {
+                self.scan.push(position)
+            }
+        }
+    }
+
+    fn collect(&self, path: &str, out: &mut Vec<Candidate>) {
+        let indexed = |route| Candidate {
+            route,
+            check_path: false,
+        };
+        if let Some(routes) = self.exact.get(path) {
+            out.extend(routes.iter().copied().map(indexed));
+        }
+        self.prefixes.collect(path, |routes| {
+            out.extend(routes.iter().copied().map(indexed))
+        });
+        out.extend(self.scan.iter().map(|&route| Candidate {
+            route,
+            check_path: true,
+        }));
+    }
+}
+
+impl RadixNode {
+    fn insert(&mut self, key: &str, position: usize) {
+        if key.is_empty() {
+            self.routes.push(position);
+            return;
+        }
+
+        for (label, child) in &mut self.children {
+            let common = common_prefix_len(label, key);
+            if common == 0 {
+                continue;
+            }
+            if common < label.len() {
+                let rest = label.split_off(common);
+                let below = std::mem::take(child);
+                child.children.push((rest, below));
+            }
+            child.insert(&key[common..], position);
+            return;
+        }
+
+        let mut leaf = RadixNode::default();
+        leaf.routes.push(position);
+        self.children.push((key.to_string(), leaf));
+    }
+
+    /// Visits the routes of every stored prefix of `path` that ends on a
+    /// segment boundary, with the same rule as [`PathMatcher::Prefix`].
+    fn collect<'a>(&'a self, path: &str, mut visit: impl FnMut(&'a [usize])) {
+        let bytes = path.as_bytes();
+        let mut node = self;
+        let mut consumed = 0;
+        loop {
+            if !node.routes.is_empty() {
+                let rest = &bytes[consumed..];
+                let on_boundary = rest.is_empty()
+                    || rest[0] == b'/'
+                    || (consumed > 0 && bytes[consumed - 1] == b'/');
+                if on_boundary {
+                    visit(&node.routes);
+                }
+            }
+
+            let rest = &path[consumed..];
+            let next = node
+                .children
+                .iter()
+                .find(|(label, _)| rest.starts_with(label.as_str()));
+            match next {
+                Some((label, child)) => {
+                    consumed += label.len();
+                    node = child;
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    let mut len = a.bytes().zip(b.bytes()).take_while(|(x, y)| x == y).count();
+    while !a.is_char_boundary(len) {
+        len -= 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(node: &RadixNode, path: &str) -> Vec<usize> {
+        let mut out = Vec::new();
+        node.collect(path, |routes| out.extend_from_slice(routes));
+        out
+    }
+
+    #[test]
+    fn radix_tree_splits_shared_prefixes() {
+        let mut root = RadixNode::default();
+        root.insert("/v1/users", 0);
+        root.insert("/v1/orders", 1);
+        root.insert("/v1", 2);
+        root.insert("/", 3);
+        root.insert("/v1/users/", 4);
+
+        assert_eq!(positions(&root, "/v1/users/7"), vec![3, 2, 0, 4]);
+        assert_eq!(positions(&root, "/v1/orders"), vec![3, 2, 1]);
+        assert_eq!(positions(&root, "/v1/usersX"), vec![3, 2]);
+        assert_eq!(positions(&root, "/v2"), vec![3]);
+    }
+
+    #[test]
+    fn radix_tree_handles_multibyte_labels() {
+        let mut root = RadixNode::default();
+        root.insert("/caf\u{e9}", 0);
+        root.insert("/caf\u{e8}", 1);
+
+        assert_eq!(positions(&root, "/caf\u{e9}/menu"), vec![0]);
+        assert_eq!(positions(&root, "/caf\u{e8}"), vec![1]);
+    }
+}
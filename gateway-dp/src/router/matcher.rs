@@ -1,81 +1,217 @@
-use super::{Route, RouteSnapshot};
+use pingora::http::RequestHeader;
+
+use url::form_urlencoded;
+
+use super::{HeaderMatcher, QueryMatcher, Route, RouteSnapshot, ValueRule};
 
 struct Candidate<'a> {
     route: &'a Route,
-    path_len: usize,
-    host_specific: bool,
+    path_rank: (u8, usize),
+    host_rank: (u8, usize),
+    header_count: usize,
+    query_count: usize,
     method_specific: bool,
 }
 
-pub fn match_route<'a>(
+/// Finds the most specific route for `request` using the snapshot's
+/// [`RouteIndex`](super::index::RouteIndex). Produces the same result as
+/// [`match_route_linear`].
+pub fn match_route<'a>(snapshot: &'a RouteSnapshot, request: &RequestHeader) -> Option<&'a Route> {
+    let path = request.uri.path();
+    let method = request.method.as_str();
+    let host = request_host(request);
+    let mut best: Option<Candidate<'a>> = None;
+
+    for candidate in snapshot.index.candidates(path, host.as_deref()) {
+        let route = &snapshot.routes[candidate.route];
+        if candidate.check_path && !route.path.as_ref().is_none_or(|p| p.matches(path)) {
+            continue;
+        }
+        let Some(host_rank) = host_rank(route, host.as_deref()) else {
+            continue;
+        };
+        if !matches_method(route, method)
+            || !matches_headers(route, request)
+            || !matches_query(route, request.uri.query())
+        {
+            continue;
+        }
+        consider(&mut best, route, host_rank);
+    }
+
+    best.map(|candidate| candidate.route)
+}
+
+/// Reference matcher that checks every route in turn. Kept for benchmarks
+/// and to cross-check the index.
+#[allow(dead_code)]
+pub fn match_route_linear<'a>(
     snapshot: &'a RouteSnapshot,
-    path: &str,
-    method: &str,
-    host: Option<&str>,
+    request: &RequestHeader,
 ) -> Option<&'a Route> {
+    let path = request.uri.path();
+    let method = request.method.as_str();
+    let host = request_host(request);
     let mut best: Option<Candidate<'a>> = None;
 
     for route in &snapshot.routes {
-        if !matches_route(route, path, method, host) {
+        let Some(host_rank) = host_rank(route, host.as_deref()) else {
+            continue;
+        };
+        if !matches_route(route, path, method)
+            || !matches_headers(route, request)
+            || !matches_query(route, request.uri.query())
+        {
             continue;
         }
+        consider(&mut best, route, host_rank);
+    }
 
-        let path_len = route.path_prefix.as_ref().map(|p| p.len()).unwrap_or(0);
-        let candidate = Candidate {
-            route,
-            path_len,
-            host_specific: route.host.is_some(),
-            method_specific: !route.methods.is_empty(),
-        };
+    best.map(|candidate| candidate.route)
+}
 
-        match &best {
-            None => best = Some(candidate),
-            Some(current) => {
-                if is_better(&candidate, current) {
-                    best = Some(candidate);
-                }
+fn request_host(request: &RequestHeader) -> Option<String> {
+    request
+        .headers
+        .get("host")
+        .and_then(|value| value.to_str().ok())
+        .map(normalize_host)
+}
+
+/// The best rank among the route's host patterns for a normalized request
+/// host, `(0, 0)` for a route without hosts, or `None` when none match.
+fn host_rank(route: &Route, host: Option<&str>) -> Option<(u8, usize)> {
+    if route.hosts.is_empty() {
+        return Some((0, 0));
+    }
+    let host = host?;
+    route.hosts.iter().filter_map(|h| h.rank(host)).max()
+}
+
+fn consider<'a>(best: &mut Option<Candidate<'a>>, route: &'a Route, host_rank: (u8, usize)) {
+    let candidate = Candidate {
+        route,
+        path_rank: route.path.as_ref().map(|p| p.rank()).unwrap_or((0, 0)),
+        host_rank,
+        header_count: route.headers.len(),
+        query_count: route.query.len(),
+        method_specific: !route.methods.is_empty(),
+    };
+
+    match best {
+        None => *best = Some(candidate),
+        Some(current) => {
+            if is_better(&candidate, current) {
+                *best = Some(candidate);
             }
         }
     }
-
-    best.map(|candidate| candidate.route)
 }
 
-fn matches_route(route: &Route, path: &str, method: &str, host: Option<&str>) -> bool {
-    if let Some(prefix) = &route.path_prefix {
-        if !path.starts_with(prefix) {
+fn matches_route(route: &Route, path: &str, method: &str) -> bool {
+    if let Some(matcher) = &route.path {
+        if !matcher.matches(path) {
             return false;
         }
     }
 
-    if !route.methods.is_empty()
-        && !route
+    matches_method(route, method)
+}
+
+fn matches_method(route: &Route, method: &str) -> bool {
+    route.methods.is_empty()
+        || route
             .methods
             .iter()
             .any(|route_method| route_method.eq_ignore_ascii_case(method))
-    {
-        return false;
+}
+
+fn matches_headers(route: &Route, request: &RequestHeader) -> bool {
+    route
+        .headers
+        .iter()
+        .all(|matcher| matches_header(matcher, request))
+}
+
+/// A repeated header matches when any of its values does.
+fn matches_header(matcher: &HeaderMatcher, request: &RequestHeader) -> bool {
+    let mut values = request
+        .headers
+        .get_all(matcher.name.as_str())
+        .iter()
+        .peekable();
+    match &matcher.rule {
+        ValueRule::Present => values.peek().is_some(),
+        ValueRule::Absent => values.peek().is_none(),
+        rule => values.any(|value| {
+            value
+                .to_str()
+                .is_ok_and(|value| matches_value(rule, value, matcher.ignore_case))
+        }),
     }
+}
 
-    if let Some(route_host) = &route.host {
-        let request_host = match host {
-            Some(value) => normalize_host(value),
-            None => return false,
-        };
-        if normalize_host(route_host) != request_host {
-            return false;
-        }
+fn matches_query(route: &Route, query: Option<&str>) -> bool {
+    if route.query.is_empty() {
+        return true;
     }
+    let params: Vec<(String, String)> = form_urlencoded::parse(query.unwrap_or("").as_bytes())
+        .into_owned()
+        .collect();
+    route
+        .query
+        .iter()
+        .all(|matcher| matches_param(matcher, &params))
+}
+
+/// A repeated parameter matches when any of its values does.
+fn matches_param(matcher: &QueryMatcher, params: &[(String, String)]) -> bool {
+    let mut values = params
+        .iter()
+        .filter(|(name, _)| *name == matcher.name)
+        .map(|(_, value)| value.as_str())
+        .peekable();
+    match &matcher.rule {
+        ValueRule::Present => values.peek().is_some(),
+        ValueRule::Absent => values.peek().is_none(),
+        rule => values.any(|value| matches_value(rule, value, false)),
+    }
+}
 
-    true
+fn matches_value(rule: &ValueRule, value: &str, ignore_case: bool) -> bool {
+    let folded;
+    let value = if ignore_case {
+        folded = value.to_lowercase();
+        folded.as_str()
+    } else {
+        value
+    };
+    match rule {
+        ValueRule::Exact(expected) => value == expected,
+        ValueRule::Prefix(prefix) => value.starts_with(prefix.as_str()),
+        ValueRule::Regex(regex) => regex.is_match(value),
+        ValueRule::Present => true,
+        ValueRule::Absent => false,
+    }
 }
 
+/// Route precedence, most significant first: path rule (see
+/// [`PathMatcher::rank`](super::PathMatcher::rank)), host rule (see
+/// [`HostMatcher::rank`](super::HostMatcher::rank); any host ranks above
+/// none), more header rules, more query rules, a method list over any method, and
+/// finally the lower route id so the choice is stable across snapshots.
 fn is_better(a: &Candidate<'_>, b: &Candidate<'_>) -> bool {
-    if a.path_len != b.path_len {
-        return a.path_len > b.path_len;
+    if a.path_rank != b.path_rank {
+        return a.path_rank > b.path_rank;
+    }
+    if a.host_rank != b.host_rank {
+        return a.host_rank > b.host_rank;
     }
-    if a.host_specific != b.host_specific {
-        return a.host_specific && !b.host_specific;
+    if a.header_count != b.header_count {
+        return a.header_count > b.header_count;
+    }
+    if a.query_count != b.query_count {
+        return a.query_count > b.query_count;
     }
     if a.method_specific != b.method_specific {
         return a.method_specific && !b.method_specific;
@@ -83,7 +219,7 @@ fn is_better(a: &Candidate<'_>, b: &Candidate<'_>) -> bool {
     a.route.id < b.route.id
 }
 
-fn normalize_host(value: &str) -> String {
+pub(super) fn normalize_host(value: &str) -> String {
     let trimmed = value.trim().to_ascii_lowercase();
     if trimmed.starts_with('[') {
         if let Some(end) = trimmed.find(']') {
@@ -101,7 +237,31 @@ fn normalize_host(value: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::router::Upstream;
+    use crate::router::{HostMatcher, PathMatcher, PathTemplate, Upstream};
+    use regex::Regex;
+
+    fn request(method: &str, path: &str, host: Option<&str>) -> RequestHeader {
+        let mut request = RequestHeader::build(method, path.as_bytes(), None).unwrap();
+        if let Some(host) = host {
+            request.insert_header("host", host).unwrap();
+        }
+        request
+    }
+
+    fn param(name: &str, rule: ValueRule) -> QueryMatcher {
+        QueryMatcher {
+            name: name.to_string(),
+            rule,
+        }
+    }
+
+    fn header(name: &str, rule: ValueRule, ignore_case: bool) -> HeaderMatcher {
+        HeaderMatcher {
+            name: name.to_string(),
+            rule,
+            ignore_case,
+        }
+    }
 
     fn route(id: &str, path: Option<&str>, methods: &[&str], host: Option<&str>) -> Route {
         Route::new(
@@ -109,72 +269,361 @@ mod tests {
             path.map(ToString::to_string),
             methods.iter().map(|m| m.to_string()).collect(),
             host.map(ToString::to_string),
-            vec![Upstream {
-                url: "http://127.0.0.1:9000".to_string(),
-            }],
+            vec![Upstream::new("http://127.0.0.1:9000".to_string())],
         )
     }
 
     #[test]
     fn selects_longest_path_prefix() {
-        let snapshot = RouteSnapshot {
-            routes: vec![
-                route("generic", Some("/v1/users"), &["GET"], None),
-                route("specific", Some("/v1/users/profile"), &["GET"], None),
-            ],
-        };
+        let snapshot = RouteSnapshot::new(vec![
+            route("generic", Some("/v1/users"), &["GET"], None),
+            route("specific", Some("/v1/users/profile"), &["GET"], None),
+        ]);
 
-        let selected = match_route(&snapshot, "/v1/users/profile", "GET", None).unwrap();
+        let selected = match_route(&snapshot, &request("GET", "/v1/users/profile", None)).unwrap();
         assert_eq!(selected.id, "specific");
     }
 
     #[test]
     fn selects_method_specific_over_wildcard() {
-        let snapshot = RouteSnapshot {
-            routes: vec![
-                route("any", Some("/v1/resource"), &[], None),
-                route("get", Some("/v1/resource"), &["GET"], None),
-            ],
-        };
+        let snapshot = RouteSnapshot::new(vec![
+            route("any", Some("/v1/resource"), &[], None),
+            route("get", Some("/v1/resource"), &["GET"], None),
+        ]);
 
-        let selected = match_route(&snapshot, "/v1/resource", "GET", None).unwrap();
+        let selected = match_route(&snapshot, &request("GET", "/v1/resource", None)).unwrap();
         assert_eq!(selected.id, "get");
     }
 
     #[test]
     fn selects_host_specific_over_wildcard() {
-        let snapshot = RouteSnapshot {
-            routes: vec![
-                route("any-host", Some("/v1/resource"), &["GET"], None),
-                route(
-                    "host-a",
-                    Some("/v1/resource"),
-                    &["GET"],
-                    Some("api.example.com"),
-                ),
-            ],
-        };
+        let snapshot = RouteSnapshot::new(vec![
+            route("any-host", Some("/v1/resource"), &["GET"], None),
+            route(
+                "host-a",
+                Some("/v1/resource"),
+                &["GET"],
+                Some("api.example.com"),
+            ),
+        ]);
 
         let selected = match_route(
             &snapshot,
-            "/v1/resource",
-            "GET",
-            Some("api.example.com:443"),
+            &request("GET", "/v1/resource", Some("api.example.com:443")),
         )
         .unwrap();
         assert_eq!(selected.id, "host-a");
     }
 
+    #[test]
+    fn exact_host_outranks_wildcards_and_longer_suffix_wins() {
+        let snapshot = RouteSnapshot::new(vec![
+            route("suffix", Some("/v1"), &[], Some(".example.com")),
+            route("wildcard", Some("/v1"), &[], Some("*.eu.example.com")),
+            route("exact", Some("/v1"), &[], Some("api.eu.example.com")),
+        ]);
+        let selected = |host| match_route(&snapshot, &request("GET", "/v1", Some(host))).unwrap();
+
+        assert_eq!(selected("api.eu.example.com").id, "exact");
+        assert_eq!(selected("web.eu.example.com").id, "wildcard");
+        assert_eq!(selected("eu.example.com").id, "suffix");
+        assert_eq!(selected("example.com").id, "suffix");
+        assert!(match_route(&snapshot, &request("GET", "/v1", Some("example.org"))).is_none());
+    }
+
+    #[test]
+    fn any_listed_host_matches() {
+        let mut listed = route("listed", Some("/v1"), &[], None);
+        listed.hosts = vec![
+            HostMatcher::parse("api.example.com"),
+            HostMatcher::parse("*.example.net"),
+        ];
+        let snapshot = RouteSnapshot::new(vec![listed]);
+
+        for host in ["api.example.com", "a.example.net:8443"] {
+            let selected = match_route(&snapshot, &request("GET", "/v1", Some(host)));
+            assert_eq!(selected.map(|r| r.id.as_str()), Some("listed"), "{host}");
+        }
+        assert!(match_route(&snapshot, &request("GET", "/v1", Some("example.net"))).is_none());
+        assert!(match_route(&snapshot, &request("GET", "/v1", None)).is_none());
+    }
+
     #[test]
     fn tie_breaks_by_route_id_for_stability() {
-        let snapshot = RouteSnapshot {
-            routes: vec![
-                route("route-b", Some("/v1/resource"), &["GET"], None),
-                route("route-a", Some("/v1/resource"), &["GET"], None),
-            ],
-        };
+        let snapshot = RouteSnapshot::new(vec![
+            route("route-b", Some("/v1/resource"), &["GET"], None),
+            route("route-a", Some("/v1/resource"), &["GET"], None),
+        ]);
 
-        let selected = match_route(&snapshot, "/v1/resource", "GET", None).unwrap();
+        let selected = match_route(&snapshot, &request("GET", "/v1/resource", None)).unwrap();
         assert_eq!(selected.id, "route-a");
     }
+
+    #[test]
+    fn header_rules_select_route() {
+        let mut v2 = route("v2", Some("/api"), &[], None);
+        v2.headers = vec![header(
+            "x-api-version",
+            ValueRule::Exact("2".to_string()),
+            false,
+        )];
+        let mut tenant = route("tenant", Some("/api"), &[], None);
+        tenant.headers = vec![header(
+            "x-tenant",
+            ValueRule::Prefix("acme-".to_string()),
+            true,
+        )];
+        let snapshot =
+            RouteSnapshot::new(vec![route("default", Some("/api"), &[], None), v2, tenant]);
+
+        let mut req = request("GET", "/api/items", None);
+        assert_eq!(match_route(&snapshot, &req).unwrap().id, "default");
+
+        req.insert_header("X-Api-Version", "2").unwrap();
+        assert_eq!(match_route(&snapshot, &req).unwrap().id, "v2");
+
+        let mut req = request("GET", "/api/items", None);
+        req.insert_header("x-tenant", "ACME-eu").unwrap();
+        assert_eq!(match_route(&snapshot, &req).unwrap().id, "tenant");
+    }
+
+    #[test]
+    fn presence_absence_and_regex_rules() {
+        let mut debug = route("debug", None, &[], None);
+        debug.headers = vec![
+            header("x-debug", ValueRule::Present, false),
+            header("x-legacy", ValueRule::Absent, false),
+        ];
+        let mut versioned = route("versioned", None, &[], None);
+        versioned.headers = vec![header(
+            "accept",
+            ValueRule::Regex(Regex::new(r"version=[23]").unwrap()),
+            false,
+        )];
+        let snapshot = RouteSnapshot::new(vec![debug, versioned]);
+
+        let mut req = request("GET", "/", None);
+        req.insert_header("x-debug", "").unwrap();
+        assert_eq!(match_route(&snapshot, &req).unwrap().id, "debug");
+
+        req.insert_header("x-legacy", "1").unwrap();
+        assert!(match_route(&snapshot, &req).is_none());
+
+        let mut req = request("GET", "/", None);
+        req.append_header("accept", "text/html").unwrap();
+        req.append_header("accept", "application/json; version=3")
+            .unwrap();
+        assert_eq!(match_route(&snapshot, &req).unwrap().id, "versioned");
+    }
+
+    #[test]
+    fn more_header_rules_rank_higher_after_host() {
+        let mut one = route("one", Some("/v1"), &["GET"], None);
+        one.headers = vec![header("x-tenant", ValueRule::Present, false)];
+        let mut two = route("two", Some("/v1"), &[], None);
+        two.headers = vec![
+            header("x-tenant", ValueRule::Present, false),
+            header("x-api-version", ValueRule::Present, false),
+        ];
+        let snapshot = RouteSnapshot::new(vec![
+            one,
+            two,
+            route("host", Some("/v1"), &[], Some("a.example.com")),
+        ]);
+
+        let mut req = request("GET", "/v1", None);
+        req.insert_header("x-tenant", "acme").unwrap();
+        req.insert_header("x-api-version", "2").unwrap();
+        assert_eq!(match_route(&snapshot, &req).unwrap().id, "two");
+
+        req.insert_header("host", "a.example.com").unwrap();
+        assert_eq!(match_route(&snapshot, &req).unwrap().id, "host");
+    }
+
+    #[test]
+    fn query_rules_split_by_version() {
+        let mut v2 = route("v2", Some("/api"), &[], None);
+        v2.query = vec![param("version", ValueRule::Exact("2".to_string()))];
+        let mut beta = route("beta", Some("/api"), &[], None);
+        beta.query = vec![
+            param(
+                "channel",
+                ValueRule::Regex(Regex::new("^beta-[0-9]+$").unwrap()),
+            ),
+            param("legacy", ValueRule::Absent),
+        ];
+        let snapshot =
+            RouteSnapshot::new(vec![route("default", Some("/api"), &[], None), v2, beta]);
+
+        let select = |uri: &str| {
+            match_route(&snapshot, &request("GET", uri, None))
+                .unwrap()
+                .id
+                .clone()
+        };
+        assert_eq!(select("/api/items"), "default");
+        assert_eq!(select("/api/items?version=2"), "v2");
+        assert_eq!(select("/api/items?version=1&version=2"), "v2");
+        assert_eq!(select("/api/items?version=20"), "default");
+        assert_eq!(select("/api/items?channel=beta%2D7"), "beta");
+        assert_eq!(select("/api/items?channel=beta-7&legacy"), "default");
+    }
+
+    #[test]
+    fn query_presence_counts_towards_specificity() {
+        let mut debug = route("debug", Some("/api"), &["GET"], None);
+        debug.query = vec![param("debug", ValueRule::Present)];
+        let snapshot = RouteSnapshot::new(vec![route("get", Some("/api"), &["GET"], None), debug]);
+
+        let selected = match_route(&snapshot, &request("GET", "/api?debug", None)).unwrap();
+        assert_eq!(selected.id, "debug");
+    }
+
+    fn with_path(id: &str, path: PathMatcher) -> Route {
+        let mut route = route(id, None, &[], None);
+        route.path = Some(path);
+        route
+    }
+
+    #[test]
+    fn prefix_does_not_match_partial_segment() {
+        let snapshot = RouteSnapshot::new(vec![route("users", Some("/v1/users"), &[], None)]);
+
+        assert!(match_route(&snapshot, &request("GET", "/v1/users/7", None)).is_some());
+        assert!(match_route(&snapshot, &request("GET", "/v1/usersX", None)).is_none());
+    }
+
+    #[test]
+    fn path_kinds_follow_documented_precedence() {
+        let mut routes = vec![
+            route("prefix", Some("/users/42/orders"), &[], None),
+            with_path(
+                "regex",
+                PathMatcher::Regex(Regex::new("^/users/[0-9]+/orders$").unwrap()),
+            ),
+            with_path(
+                "template",
+                PathMatcher::Template(PathTemplate::parse("/users/{id}/orders").unwrap()),
+            ),
+            with_path("exact", PathMatcher::Exact("/users/42/orders".to_string())),
+        ];
+        let select = |routes: &[Route]| {
+            let snapshot = RouteSnapshot::new(routes.to_vec());
+            match_route(&snapshot, &request("GET", "/users/42/orders", None))
+                .unwrap()
+                .id
+                .clone()
+        };
+
+        assert_eq!(select(&routes), "exact");
+        routes.pop();
+        assert_eq!(select(&routes), "template");
+        routes.pop();
+        assert_eq!(select(&routes), "regex");
+        routes.pop();
+        assert_eq!(select(&routes), "prefix");
+    }
+
+    #[test]
+    fn path_rank_outweighs_host_and_method() {
+        let snapshot = RouteSnapshot::new(vec![
+            route("host", Some("/v1"), &["GET"], Some("api.example.com")),
+            with_path("exact", PathMatcher::Exact("/v1/items".to_string())),
+        ]);
+
+        let selected = match_route(
+            &snapshot,
+            &request("GET", "/v1/items", Some("api.example.com")),
+        )
+        .unwrap();
+        assert_eq!(selected.id, "exact");
+    }
+
+    #[test]
+    fn index_agrees_with_linear_scan() {
+        let hosts = [
+            None,
+            Some("a.example.com"),
+            Some("B.example.com:8443"),
+            Some("*.example.com"),
+            Some(".example.com"),
+            Some("*.com"),
+        ];
+        let paths = [
+            None,
+            Some(PathMatcher::Prefix("/".to_string())),
+            Some(PathMatcher::Prefix("/v1".to_string())),
+            Some(PathMatcher::Prefix("/v1/users".to_string())),
+            Some(PathMatcher::Prefix("/v1/users/".to_string())),
+            Some(PathMatcher::Exact("/v1/users".to_string())),
+            Some(PathMatcher::Template(
+                PathTemplate::parse("/v1/{resource}").unwrap(),
+            )),
+            Some(PathMatcher::Regex(Regex::new("^/v[12]/u").unwrap())),
+        ];
+        let methods: [&[&str]; 3] = [&[], &["GET"], &["post"]];
+
+        // Small LCG so the test is deterministic without extra dependencies.
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |bound: usize| {
+            seed = seed
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            ((seed >> 33) as usize) % bound
+        };
+
+        let routes: Vec<Route> = (0..200)
+            .map(|i| {
+                let mut route = route(
+                    &format!("r{:02}", next(40)),
+                    None,
+                    methods[next(methods.len())],
+                    hosts[next(hosts.len())],
+                );
+                route.path = paths[next(paths.len())].clone();
+                if i % 7 == 0 {
+                    route.headers = vec![header("x-tenant", ValueRule::Present, false)];
+                }
+                route
+            })
+            .collect();
+        let snapshot = RouteSnapshot::new(routes);
+
+        let request_paths = [
+            "/",
+            "/v1",
+            "/v1/",
+            "/v1/users",
+            "/v1/users/7",
+            "/v1/usersX",
+            "/v2/users",
+            "/other",
+        ];
+        let request_hosts = [
+            None,
+            Some("a.example.com"),
+            Some("b.example.com"),
+            Some("c.example.com"),
+            Some("example.com"),
+            Some("x.a.example.com"),
+        ];
+        for path in request_paths {
+            for host in request_hosts {
+                for method in ["GET", "POST", "DELETE"] {
+                    for tenant in [false, true] {
+                        let mut req = request(method, path, host);
+                        if tenant {
+                            req.insert_header("x-tenant", "acme").unwrap();
+                        }
+                        let indexed = match_route(&snapshot, &req).map(|r| r as *const Route);
+                        let linear = match_route_linear(&snapshot, &req).map(|r| r as *const Route);
+                        assert_eq!(
+                            indexed, linear,
+                            "{method} {path} host={host:?} tenant={tenant}"
+                        );
+                    }
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,121 @@
+use regex::Regex;
+
+pub use gateway_proto::path::PathTemplate;
+
+/// How a route matches the request path.
+///
+/// When several routes match, [`PathMatcher::rank`] orders them: exact beats
+/// template, template beats regex, regex beats prefix, and any path rule
+/// beats none. Within a kind the more specific rule wins: the longer exact
+/// path or prefix, the template with more literal characters, the longer
+/// regex. Remaining ties fall through to host, headers, query and method.
+#[derive(Clone, Debug)]
+pub enum PathMatcher {
+    Exact(String),
+    /// Segment-aware prefix: `/v1/users` matches `/v1/users` and
+    /// `/v1/users/42` but not `/v1/usersX`.
+    Prefix(String),
+    Regex(Regex),
+    Template(PathTemplate),
+}
+
+impl PathMatcher {
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            Self::Exact(expected) => path == expected,
+            Self::Prefix(prefix) => matches_prefix(prefix, path),
+            Self::Regex(regex) => regex.is_match(path),
+            Self::Template(template) => template.captures(path).is_some(),
+        }
+    }
+
+    /// Variables captured from `path` by a template; empty for other kinds.
+    pub fn captures(&self, path: &str) -> Vec<(String, String)> {
+        match self {
+            Self::Template(template) => template.captures(path).unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Precedence key, compared lexicographically; larger is more specific.
+    pub fn rank(&self) -> (u8, usize) {
+        match self {
+            Self::Exact(path) => (4, path.len()),
+            Self::Template(template) => (3, template.literal_len()),
+            Self::Regex(regex) => (2, regex.as_str().len()),
+            Self::Prefix(prefix) => (1, prefix.len()),
+        }
+    }
+}
+
+fn matches_prefix(prefix: &str, path: &str) -> bool {
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || prefix.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(value: &str) -> PathMatcher {
+        PathMatcher::Template(PathTemplate::parse(value).unwrap())
+    }
+
+    #[test]
+    fn prefix_respects_segment_boundaries() {
+        let prefix = PathMatcher::Prefix("/v1/users".to_string());
+        assert!(prefix.matches("/v1/users"));
+        assert!(prefix.matches("/v1/users/42"));
+        assert!(!prefix.matches("/v1/usersX"));
+
+        let slash = PathMatcher::Prefix("/v1/".to_string());
+        assert!(slash.matches("/v1/users"));
+        assert!(!slash.matches("/v1"));
+        assert!(PathMatcher::Prefix("/".to_string()).matches("/anything"));
+    }
+
+    #[test]
+    fn exact_and_regex() {
+        let exact = PathMatcher::Exact("/healthz".to_string());
+        assert!(exact.matches("/healthz"));
+        assert!(!exact.matches("/healthz/"));
+
+        let regex = PathMatcher::Regex(Regex::new(r"^/v[0-9]+/items$").unwrap());
+        assert!(regex.matches("/v12/items"));
+        assert!(!regex.matches("/vX/items"));
+    }
+
+    #[test]
+    fn template_captures_variables() {
+        let orders = template("/users/{id}/orders/{order_id}");
+        assert_eq!(
+            orders.captures("/users/42/orders/7"),
+            vec![
+                ("id".to_string(), "42".to_string()),
+                ("order_id".to_string(), "7".to_string()),
+            ]
+        );
+        assert!(!orders.matches("/users/42/orders"));
+        assert!(!orders.matches("/users//orders/7"));
+        assert!(!orders.matches("/users/42/orders/7/items"));
+        assert!(PathMatcher::Exact("/x".to_string())
+            .captures("/x")
+            .is_empty());
+    }
+
+    #[test]
+    fn rank_orders_kinds_then_specificity() {
+        let exact = PathMatcher::Exact("/a".to_string());
+        let long_template = template("/users/{id}/orders");
+        let short_template = template("/users/{id}");
+        let regex = PathMatcher::Regex(Regex::new("^/users/.*").unwrap());
+        let prefix = PathMatcher::Prefix("/users/42/orders/and/more".to_string());
+
+        assert!(exact.rank() > long_template.rank());
+        assert!(long_template.rank() > short_template.rank());
+        assert!(short_template.rank() > regex.rank());
+        assert!(regex.rank() > prefix.rank());
+    }
+}
@@ -0,0 +1,116 @@
+use regex::Regex;
+
+/// Changes applied to the upstream request after a route matched. The
+/// downstream request is left as received.
+#[derive(Clone, Debug, Default)]
+pub struct Rewrite {
+    pub path: Option<PathRewrite>,
+    /// Replaces the `Host` header sent upstream.
+    pub host: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub enum PathRewrite {
+    /// Removes a leading prefix that ends on a segment boundary; an empty
+    /// remainder becomes `/`.
+    StripPrefix(String),
+    /// Swaps a leading prefix, with the same boundary rule as `StripPrefix`.
+    ReplacePrefix { prefix: String, replacement: String },
+    /// Replaces every match of `pattern`; `substitution` may refer to
+    /// captures as `$1` or `${name}`.
+    Regex {
+        pattern: Regex,
+        substitution: String,
+    },
+}
+
+impl Rewrite {
+    /// The path to send upstream, or `None` when it stays unchanged.
+    pub fn apply_path(&self, path: &str) -> Option<String> {
+        match self.path.as_ref()? {
+            PathRewrite::StripPrefix(prefix) => replace_prefix(path, prefix, ""),
+            PathRewrite::ReplacePrefix {
+                prefix,
+                replacement,
+            } => replace_prefix(path, prefix, replacement),
+            PathRewrite::Regex {
+                pattern,
+                substitution,
+            } => {
+                let rewritten = pattern.replace_all(path, substitution.as_str());
+                (rewritten != path).then(|| rewritten.into_owned())
+            }
+        }
+    }
+}
+
+fn replace_prefix(path: &str, prefix: &str, replacement: &str) -> Option<String> {
+    let rest = path.strip_prefix(prefix)?;
+    let on_boundary = rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/');
+    if !on_boundary {
+        return None;
+    }
+
+    let mut rewritten = replacement.trim_end_matches('/').to_string();
+    if !rest.is_empty() && !rest.starts_with('/') {
+        rewritten.push('/');
+    }
+    rewritten.push_str(rest);
+    if !rewritten.starts_with('/') {
+        rewritten.insert(0, '/');
+    }
+    Some(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(path: PathRewrite) -> Rewrite {
+        Rewrite {
+            path: Some(path),
+            host: None,
+        }
+    }
+
+    #[test]
+    fn strips_prefix_on_segment_boundary() {
+        let strip = rewrite(PathRewrite::StripPrefix("/api".to_string()));
+        assert_eq!(strip.apply_path("/api/orders").as_deref(), Some("/orders"));
+        assert_eq!(strip.apply_path("/api").as_deref(), Some("/"));
+        assert_eq!(strip.apply_path("/apix/orders"), None);
+        assert_eq!(strip.apply_path("/other"), None);
+
+        let trailing = rewrite(PathRewrite::StripPrefix("/api/".to_string()));
+        assert_eq!(
+            trailing.apply_path("/api/orders/7").as_deref(),
+            Some("/orders/7")
+        );
+    }
+
+    #[test]
+    fn replaces_prefix() {
+        let replace = rewrite(PathRewrite::ReplacePrefix {
+            prefix: "/api".to_string(),
+            replacement: "/v2/".to_string(),
+        });
+        assert_eq!(
+            replace.apply_path("/api/orders").as_deref(),
+            Some("/v2/orders")
+        );
+        assert_eq!(replace.apply_path("/api").as_deref(), Some("/v2"));
+    }
+
+    #[test]
+    fn regex_substitution_uses_captures() {
+        let regex = rewrite(PathRewrite::Regex {
+            pattern: Regex::new(r"^/users/(?P<id>\d+)/orders/(\d+)$").unwrap(),
+            substitution: "/orders/$2?user=${id}".to_string(),
+        });
+        assert_eq!(
+            regex.apply_path("/users/7/orders/42").as_deref(),
+            Some("/orders/42?user=7")
+        );
+        assert_eq!(regex.apply_path("/users/x/orders/42"), None);
+    }
+}
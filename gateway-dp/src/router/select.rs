@@ -1,10 +1,444 @@
+use super::hash::mix64;
 use super::{Route, Upstream};
 use std::sync::atomic::Ordering;
 
-pub fn select_upstream(route: &Route) -> Option<Upstream> {
-    if route.upstreams.is_empty() {
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LbPolicy {
+    #[default]
+    RoundRobin,
+    WeightedRoundRobin,
+    /// Consistent hash ring over `route.hash_key`.
+    RingHash,
+    /// Maglev consistent hashing over `route.hash_key`.
+    Maglev,
+    /// The less loaded of two random upstreams by in-flight requests.
+    LeastRequest,
+    /// The cheaper of two random upstreams by peak EWMA latency times
+    /// in-flight requests.
+    PeakEwma,
+}
+
+impl LbPolicy {
+    /// Resolves the route `lb` string. An empty value selects the default
+    /// round robin policy; unknown names return `None`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "round_robin" => Some(Self::RoundRobin),
+            "weighted" | "weighted_round_robin" => Some(Self::WeightedRoundRobin),
+            "ring_hash" => Some(Self::RingHash),
+            "maglev" => Some(Self::Maglev),
+            "least_request" => Some(Self::LeastRequest),
+            "peak_ewma" => Some(Self::PeakEwma),
+            _ => None,
+        }
+    }
+
+    /// Whether upstream weights shape the split, so weight 0 upstreams get
+    /// no traffic.
+    fn uses_weights(self) -> bool {
+        self != Self::RoundRobin
+    }
+}
+
+/// Picks an upstream for `route`, only among the upstreams of `subset` when
+/// set and never one whose URL is in `exclude`, so failover attempts go to a
+/// different upstream. Only the lowest-numbered priority tier that still has
+/// an available member is considered; when every member of a tier is
+/// unhealthy, ejected or has an open circuit, traffic spills over to the
+/// next tier.
+pub fn select_upstream_excluding(
+    route: &Route,
+    subset: Option<&str>,
+    exclude: &[String],
+) -> Option<Upstream> {
+    select_upstream_hashed(route, subset, exclude, None)
+}
+
+/// Like [`select_upstream_excluding`], with the request's `hash` for the
+/// hashing policies. When the hashed upstream is unavailable the next one
+/// along the ring or table is used; requests without a hash are spread
+/// round robin. When `subset` has no available upstream, the route split's
+/// default subset serves the request.
+pub fn select_upstream_hashed(
+    route: &Route,
+    subset: Option<&str>,
+    exclude: &[String],
+    hash: Option<u64>,
+) -> Option<Upstream> {
+    let mut candidates = active_tier(route, subset, exclude);
+    let default_subset = route
+        .split
+        .as_ref()
+        .map(|split| split.default_subset.as_str());
+    if candidates.is_empty() && subset.is_some() && subset != default_subset {
+        candidates = active_tier(route, default_subset, exclude);
+    }
+    if candidates.is_empty() {
         return None;
     }
+    let idx = match (route.lb, route.hash_table.as_deref(), hash) {
+        (LbPolicy::WeightedRoundRobin, _, _) => select_weighted(route, &candidates),
+        (LbPolicy::LeastRequest, _, _) => select_two_choices(route, &candidates, |upstream| {
+            (upstream.status.in_flight() + 1) as f64
+        }),
+        (LbPolicy::PeakEwma, _, _) => select_two_choices(route, &candidates, |upstream| {
+            upstream.status.peak_ewma_cost()
+        }),
+        (LbPolicy::RingHash | LbPolicy::Maglev, Some(table), Some(hash)) => table
+            .pick(hash, |idx| candidates.contains(&idx))
+            .unwrap_or_else(|| select_round_robin(route, &candidates)),
+        _ => select_round_robin(route, &candidates),
+    };
+    Some(route.upstreams[idx].clone())
+}
+
+fn active_tier(route: &Route, subset: Option<&str>, exclude: &[String]) -> Vec<usize> {
+    let usable = |upstream: &Upstream| {
+        (subset.is_none() || upstream.subset.as_deref() == subset)
+            && upstream.status.is_available()
+            && (!route.lb.uses_weights() || upstream.weight > 0)
+            && !exclude.contains(&upstream.url)
+    };
+    let Some(tier) = route
+        .upstreams
+        .iter()
+        .filter(|u| usable(u))
+        .map(|u| u.priority)
+        .min()
+    else {
+        return Vec::new();
+    };
+
+    route
+        .upstreams
+        .iter()
+        .enumerate()
+        .filter(|(_, u)| u.priority == tier && usable(u))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+fn select_round_robin(route: &Route, candidates: &[usize]) -> usize {
     let idx = route.rr_index.fetch_add(1, Ordering::Relaxed);
-    Some(route.upstreams[idx % route.upstreams.len()].clone())
+    candidates[idx % candidates.len()]
+}
+
+/// Power of two choices: of two distinct candidates drawn at random, the
+/// one with the lower `cost` per unit of weight. Cheaper than a full scan
+/// and avoids every balancer herding onto the same least loaded upstream.
+fn select_two_choices(
+    route: &Route,
+    candidates: &[usize],
+    cost: impl Fn(&Upstream) -> f64,
+) -> usize {
+    if candidates.len() == 1 {
+        return candidates[0];
+    }
+    let draw = mix64(route.rr_index.fetch_add(1, Ordering::Relaxed) as u64);
+    let len = candidates.len() as u64;
+    let first = (draw % len) as usize;
+    let second = (first + 1 + ((draw >> 32) % (len - 1)) as usize) % candidates.len();
+
+    let weighted = |idx: usize| {
+        let upstream = &route.upstreams[idx];
+        cost(upstream) / f64::from(upstream.weight.max(1))
+    };
+    let (a, b) = (candidates[first], candidates[second]);
+    if weighted(b) < weighted(a) {
+        b
+    } else {
+        a
+    }
+}
+
+/// Smooth weighted round robin (as in nginx): every pick raises each
+/// candidate's current weight by its configured weight, selects the largest,
+/// then lowers the winner by the total. Picks are spread out instead of
+/// being sent in bursts. Upstreams with weight 0 receive no traffic.
+fn select_weighted(route: &Route, candidates: &[usize]) -> usize {
+    let mut current = route
+        .wrr_current
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let mut total: i64 = 0;
+    let mut best = candidates[0];
+    for &idx in candidates {
+        let weight = i64::from(route.upstreams[idx].weight);
+        current[idx] += weight;
+        total += weight;
+        if current[idx] > current[best] {
+            best = idx;
+        }
+    }
+
+    current[best] -= total;
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::hash::{hash_bytes, HashTable};
+    use crate::router::TrafficSplit;
+    use std::sync::Arc;
+
+    fn weighted_route(weights: &[u32]) -> Route {
+        let upstreams = weights
+            .iter()
+            .enumerate()
+            .map(|(idx, weight)| Upstream {
+                weight: *weight,
+                ..Upstream::new(format!("http://10.0.0.{idx}:8080"))
+            })
+            .collect();
+        let mut route = Route::new("weighted".to_string(), None, Vec::new(), None, upstreams);
+        route.lb = LbPolicy::WeightedRoundRobin;
+        route
+    }
+
+    fn tiered_route(priorities: &[u32]) -> Route {
+        let upstreams = priorities
+            .iter()
+            .enumerate()
+            .map(|(idx, priority)| Upstream {
+                priority: *priority,
+                ..Upstream::new(format!("http://10.0.0.{idx}:8080"))
+            })
+            .collect();
+        Route::new("tiered".to_string(), None, Vec::new(), None, upstreams)
+    }
+
+    fn select_upstream(route: &Route) -> Option<Upstream> {
+        select_upstream_excluding(route, None, &[])
+    }
+
+    fn pick_counts(route: &Route, picks: usize) -> Vec<usize> {
+        let mut counts = vec![0; route.upstreams.len()];
+        for _ in 0..picks {
+            let upstream = select_upstream(route).unwrap();
+            let idx = route
+                .upstreams
+                .iter()
+                .position(|u| u.url == upstream.url)
+                .unwrap();
+            counts[idx] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn parses_lb_names() {
+        assert_eq!(LbPolicy::from_name(""), Some(LbPolicy::RoundRobin));
+        assert_eq!(
+            LbPolicy::from_name("round_robin"),
+            Some(LbPolicy::RoundRobin)
+        );
+        assert_eq!(
+            LbPolicy::from_name("Weighted_Round_Robin"),
+            Some(LbPolicy::WeightedRoundRobin)
+        );
+        assert_eq!(LbPolicy::from_name("ring_hash"), Some(LbPolicy::RingHash));
+        assert_eq!(LbPolicy::from_name("maglev"), Some(LbPolicy::Maglev));
+        assert_eq!(
+            LbPolicy::from_name("least_request"),
+            Some(LbPolicy::LeastRequest)
+        );
+        assert_eq!(LbPolicy::from_name("peak_ewma"), Some(LbPolicy::PeakEwma));
+        assert_eq!(LbPolicy::from_name("random"), None);
+    }
+
+    #[test]
+    fn round_robin_ignores_weights() {
+        let mut route = weighted_route(&[5, 95]);
+        route.lb = LbPolicy::RoundRobin;
+
+        assert_eq!(pick_counts(&route, 100), vec![50, 50]);
+    }
+
+    #[test]
+    fn weighted_distribution_converges_to_canary_split() {
+        let route = weighted_route(&[5, 95]);
+
+        assert_eq!(pick_counts(&route, 100), vec![5, 95]);
+        assert_eq!(pick_counts(&route, 10_000), vec![500, 9_500]);
+    }
+
+    #[test]
+    fn weighted_picks_are_interleaved() {
+        let route = weighted_route(&[5, 1, 1]);
+        let sequence: Vec<String> = (0..7)
+            .map(|_| select_upstream(&route).unwrap().url)
+            .collect();
+
+        let a = "http://10.0.0.0:8080";
+        let b = "http://10.0.0.1:8080";
+        let c = "http://10.0.0.2:8080";
+        assert_eq!(sequence, vec![a, a, b, a, c, a, a]);
+    }
+
+    #[test]
+    fn zero_weight_upstreams_receive_no_traffic() {
+        let route = weighted_route(&[0, 3, 1]);
+
+        assert_eq!(pick_counts(&route, 400), vec![0, 300, 100]);
+    }
+
+    #[test]
+    fn all_zero_weights_select_nothing() {
+        let route = weighted_route(&[0, 0]);
+
+        assert!(select_upstream(&route).is_none());
+    }
+
+    #[test]
+    fn only_lowest_priority_tier_receives_traffic() {
+        let route = tiered_route(&[1, 0, 0, 2]);
+
+        assert_eq!(pick_counts(&route, 100), vec![0, 50, 50, 0]);
+    }
+
+    #[test]
+    fn partially_unhealthy_tier_keeps_serving() {
+        let route = tiered_route(&[0, 0, 1]);
+        route.upstreams[0].status.set_healthy(false);
+
+        assert_eq!(pick_counts(&route, 10), vec![0, 10, 0]);
+    }
+
+    #[test]
+    fn spills_to_next_tier_when_tier_is_down() {
+        let route = tiered_route(&[0, 0, 1, 2]);
+        route.upstreams[0].status.set_healthy(false);
+        route.upstreams[1]
+            .status
+            .eject_for(std::time::Duration::from_secs(60));
+
+        assert_eq!(pick_counts(&route, 10), vec![0, 0, 10, 0]);
+
+        route.upstreams[0].status.set_healthy(true);
+        assert_eq!(pick_counts(&route, 10), vec![10, 0, 0, 0]);
+    }
+
+    #[test]
+    fn no_upstream_when_every_tier_is_down() {
+        let route = tiered_route(&[0, 1]);
+        for upstream in &route.upstreams {
+            upstream.status.set_healthy(false);
+        }
+
+        assert!(select_upstream(&route).is_none());
+    }
+
+    #[test]
+    fn weighted_selection_applies_within_tier() {
+        let mut route = weighted_route(&[1, 3, 5]);
+        route.upstreams[2].priority = 1;
+
+        assert_eq!(pick_counts(&route, 400), vec![100, 300, 0]);
+    }
+
+    #[test]
+    fn zero_weight_tier_spills_over_in_weighted_mode() {
+        let mut route = weighted_route(&[0, 2]);
+        route.upstreams[1].priority = 1;
+
+        assert_eq!(pick_counts(&route, 10), vec![0, 10]);
+    }
+
+    #[test]
+    fn excluded_upstreams_are_skipped_across_tiers() {
+        let route = tiered_route(&[0, 0, 1]);
+        let tried = vec![
+            "http://10.0.0.0:8080".to_string(),
+            "http://10.0.0.1:8080".to_string(),
+        ];
+
+        let upstream = select_upstream_excluding(&route, None, &tried).unwrap();
+        assert_eq!(upstream.url, "http://10.0.0.2:8080");
+
+        let all: Vec<String> = route.upstreams.iter().map(|u| u.url.clone()).collect();
+        assert!(select_upstream_excluding(&route, None, &all).is_none());
+    }
+
+    #[test]
+    fn least_request_never_picks_the_busiest_upstream() {
+        let mut route = tiered_route(&[0, 0, 0]);
+        route.lb = LbPolicy::LeastRequest;
+        for _ in 0..5 {
+            route.upstreams[1].status.start_request();
+        }
+
+        let counts = pick_counts(&route, 300);
+        assert_eq!(counts[1], 0, "{counts:?}");
+        assert!(counts[0] > 100 && counts[2] > 100, "{counts:?}");
+    }
+
+    #[test]
+    fn peak_ewma_prefers_the_faster_upstream() {
+        let mut route = tiered_route(&[0, 0]);
+        route.lb = LbPolicy::PeakEwma;
+        route.upstreams[0]
+            .status
+            .record_rtt(std::time::Duration::from_millis(500));
+        route.upstreams[1]
+            .status
+            .record_rtt(std::time::Duration::from_millis(5));
+
+        assert_eq!(pick_counts(&route, 10), vec![0, 10]);
+    }
+
+    #[test]
+    fn hashed_keys_stick_to_an_upstream_until_it_is_down() {
+        let mut route = tiered_route(&[0, 0, 0]);
+        route.lb = LbPolicy::Maglev;
+        route.hash_table = HashTable::build(route.lb, &route.upstreams).map(Arc::new);
+        let hash = Some(hash_bytes(b"alice"));
+
+        let first = select_upstream_hashed(&route, None, &[], hash).unwrap();
+        for _ in 0..10 {
+            assert_eq!(
+                select_upstream_hashed(&route, None, &[], hash).unwrap().url,
+                first.url
+            );
+        }
+
+        first.status.set_healthy(false);
+        let fallback = select_upstream_hashed(&route, None, &[], hash).unwrap();
+        assert_ne!(fallback.url, first.url);
+        first.status.set_healthy(true);
+        assert_eq!(
+            select_upstream_hashed(&route, None, &[], hash).unwrap().url,
+            first.url
+        );
+    }
+
+    #[test]
+    fn subset_limits_selection_and_falls_back_to_the_default() {
+        let mut route = tiered_route(&[0, 0, 0]);
+        for (upstream, subset) in route
+            .upstreams
+            .iter_mut()
+            .zip(["stable", "stable", "canary"])
+        {
+            upstream.subset = Some(subset.to_string());
+        }
+        route.split = Some(TrafficSplit {
+            rules: Vec::new(),
+            default_subset: "stable".to_string(),
+        });
+        let pick = |subset| select_upstream_excluding(&route, subset, &[]).map(|u| u.url);
+
+        for _ in 0..4 {
+            assert_eq!(pick(Some("canary")).unwrap(), "http://10.0.0.2:8080");
+            assert_ne!(pick(Some("stable")).unwrap(), "http://10.0.0.2:8080");
+        }
+
+        route.upstreams[2].status.set_healthy(false);
+        assert_ne!(pick(Some("canary")).unwrap(), "http://10.0.0.2:8080");
+
+        route.upstreams[0].status.set_healthy(false);
+        route.upstreams[1].status.set_healthy(false);
+        assert!(pick(Some("stable")).is_none());
+    }
 }
@@ -0,0 +1,176 @@
+use pingora::http::RequestHeader;
+
+use super::hash::{cookie_value, mix64, HashKey};
+
+/// XORed into request hashes before bucketing, so the split bucket of a key
+/// is independent of where `ring_hash` or `maglev` place the same key.
+const BUCKET_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Rule-based split of a route's traffic between named upstream subsets.
+/// The first matching rule picks the subset; requests matching no rule go
+/// to `default_subset`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrafficSplit {
+    pub rules: Vec<SplitRule>,
+    pub default_subset: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SplitRule {
+    pub subset: String,
+    pub condition: SplitCondition,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SplitCondition {
+    /// Lowercase header name; any value matches when `value` is unset.
+    Header { name: String, value: Option<String> },
+    /// Any value matches when `value` is unset.
+    Cookie { name: String, value: Option<String> },
+    /// Holds for `percent` of the key space. Requests without the key never
+    /// match, and a key stays in its bucket as `percent` grows.
+    HashBucket { key: HashKey, percent: f64 },
+}
+
+impl TrafficSplit {
+    /// The subset the request is sent to.
+    pub fn subset_for(&self, request: &RequestHeader, client_ip: &str) -> &str {
+        self.rules
+            .iter()
+            .find(|rule| rule.condition.matches(request, client_ip))
+            .map_or(self.default_subset.as_str(), |rule| rule.subset.as_str())
+    }
+}
+
+impl SplitCondition {
+    fn matches(&self, request: &RequestHeader, client_ip: &str) -> bool {
+        let value_matches = |actual: Option<&str>, expected: &Option<String>| match expected {
+            Some(expected) => actual == Some(expected.as_str()),
+            None => actual.is_some(),
+        };
+        match self {
+            Self::Header { name, value } => {
+                let actual = request
+                    .headers
+                    .get(name.as_str())
+                    .and_then(|actual| actual.to_str().ok());
+                value_matches(actual, value)
+            }
+            Self::Cookie { name, value } => value_matches(cookie_value(request, name), value),
+            Self::HashBucket { key, percent } => key.hash(request, client_ip).is_some_and(|hash| {
+                ((mix64(hash ^ BUCKET_SALT) % 10_000) as f64) < percent * 100.0
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(condition: SplitCondition) -> TrafficSplit {
+        TrafficSplit {
+            rules: vec![SplitRule {
+                subset: "canary".to_string(),
+                condition,
+            }],
+            default_subset: "stable".to_string(),
+        }
+    }
+
+    fn request(headers: &[(&str, &str)]) -> RequestHeader {
+        let mut request = RequestHeader::build("GET", b"/orders", None).unwrap();
+        for (name, value) in headers {
+            request.append_header(name.to_string(), *value).unwrap();
+        }
+        request
+    }
+
+    #[test]
+    fn header_rule_sends_matching_requests_to_its_subset() {
+        let split = split(SplitCondition::Header {
+            name: "x-canary".to_string(),
+            value: Some("true".to_string()),
+        });
+
+        assert_eq!(
+            split.subset_for(&request(&[("x-canary", "true")]), ""),
+            "canary"
+        );
+        assert_eq!(
+            split.subset_for(&request(&[("x-canary", "false")]), ""),
+            "stable"
+        );
+        assert_eq!(split.subset_for(&request(&[]), ""), "stable");
+    }
+
+    #[test]
+    fn cookie_rule_without_value_matches_any_value() {
+        let split = split(SplitCondition::Cookie {
+            name: "beta".to_string(),
+            value: None,
+        });
+
+        let opted_in = request(&[("cookie", "session=abc; beta=1")]);
+        assert_eq!(split.subset_for(&opted_in, ""), "canary");
+        let other = request(&[("cookie", "session=abc")]);
+        assert_eq!(split.subset_for(&other, ""), "stable");
+    }
+
+    #[test]
+    fn hash_bucket_splits_users_by_percent_and_keeps_them_when_it_grows() {
+        let bucket = |percent| {
+            split(SplitCondition::HashBucket {
+                key: HashKey::Header("x-user-id".to_string()),
+                percent,
+            })
+        };
+        let users: Vec<RequestHeader> = (0..10_000)
+            .map(|idx| request(&[("x-user-id", &format!("user-{idx}"))]))
+            .collect();
+        let canary = |split: &TrafficSplit| -> Vec<usize> {
+            (0..users.len())
+                .filter(|idx| split.subset_for(&users[*idx], "") == "canary")
+                .collect()
+        };
+
+        let five = canary(&bucket(5.0));
+        let twenty = canary(&bucket(20.0));
+        assert!(five.len().abs_diff(500) < 100, "{}", five.len());
+        assert!(twenty.len().abs_diff(2_000) < 200, "{}", twenty.len());
+        assert!(five.iter().all(|idx| twenty.contains(idx)));
+        assert!(canary(&bucket(0.0)).is_empty());
+        assert_eq!(canary(&bucket(100.0)).len(), users.len());
+        assert_eq!(bucket(100.0).subset_for(&request(&[]), ""), "stable");
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let split = TrafficSplit {
+            rules: vec![
+                SplitRule {
+                    subset: "internal".to_string(),
+                    condition: SplitCondition::Header {
+                        name: "x-internal".to_string(),
+                        value: None,
+                    },
+                },
+                SplitRule {
+                    subset: "canary".to_string(),
+                    condition: SplitCondition::Header {
+                        name: "x-canary".to_string(),
+                        value: None,
+                    },
+                },
+            ],
+            default_subset: "stable".to_string(),
+        };
+
+        let both = request(&[("x-canary", "1"), ("x-internal", "1")]);
+        assert_eq!(split.subset_for(&both, ""), "internal");
+        assert_eq!(
+            split.subset_for(&request(&[("x-canary", "1")]), ""),
+            "canary"
+        );
+    }
+}
@@ -1,19 +1,33 @@
 use arc_swap::ArcSwap;
 use std::sync::Arc;
 
+use crate::ratelimit::RateLimiter;
 use crate::router::RouteSnapshot;
+use crate::upstream::UpstreamRegistry;
 
 pub struct State {
     snapshot: ArcSwap<RouteSnapshot>,
+    upstreams: UpstreamRegistry,
+    rate_limits: RateLimiter,
 }
 
 impl State {
     pub fn new(snapshot: RouteSnapshot) -> Self {
         Self {
             snapshot: ArcSwap::from_pointee(snapshot),
+            upstreams: UpstreamRegistry::new(),
+            rate_limits: RateLimiter::new(),
         }
     }
 
+    pub fn upstreams(&self) -> &UpstreamRegistry {
+        &self.upstreams
+    }
+
+    pub fn rate_limits(&self) -> &RateLimiter {
+        &self.rate_limits
+    }
+
     pub fn snapshot(&self) -> Arc<RouteSnapshot> {
         self.snapshot.load_full()
     }
@@ -0,0 +1,212 @@
+use hmac::{Hmac, Mac};
+use pingora::http::RequestHeader;
+use sha2::Sha256;
+use std::fmt::Write as _;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::router::{cookie_value, hash_bytes, Route, Upstream};
+
+/// Signature bytes kept in the cookie; 128 bits is plenty against forgery.
+const TAG_LEN: usize = 16;
+
+/// A route's sticky sessions: the upstream picked for a client's first
+/// request is named in cookie `cookie`, and later requests carrying it go
+/// back to that upstream while it stays available.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StickySession {
+    pub cookie: String,
+    /// `Max-Age` of the cookie; a browser session cookie when unset.
+    pub ttl: Option<Duration>,
+}
+
+impl StickySession {
+    /// The `Set-Cookie` value pinning the client to `value`.
+    pub fn set_cookie(&self, value: &str) -> String {
+        let mut cookie = format!("{}={value}; Path=/; HttpOnly; SameSite=Lax", self.cookie);
+        if let Some(ttl) = self.ttl {
+            let _ = write!(cookie, "; Max-Age={}", ttl.as_secs().max(1));
+        }
+        cookie
+    }
+}
+
+/// Signs and verifies sticky cookies. Every data plane behind the same
+/// load balancer needs the same secret to honor each other's cookies.
+#[derive(Clone)]
+pub struct CookieSigner {
+    mac: Hmac<Sha256>,
+}
+
+impl CookieSigner {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            mac: Hmac::new_from_slice(secret).expect("HMAC accepts keys of any length"),
+        }
+    }
+
+    /// A signer with a key local to this process, for when no secret is
+    /// configured. Cookies stop verifying when the process restarts.
+    pub fn random() -> Self {
+        let mut secret = Vec::with_capacity(32);
+        secret.extend_from_slice(Uuid::new_v4().as_bytes());
+        secret.extend_from_slice(Uuid::new_v4().as_bytes());
+        Self::new(&secret)
+    }
+
+    /// Cookie value naming `upstream` for `route_id`. Carries a hash of the
+    /// URL rather than the URL, so upstream addresses are not disclosed.
+    pub fn cookie_value(&self, route_id: &str, upstream: &str) -> String {
+        let id = upstream_id(upstream);
+        let mut value = format!("{id:016x}.");
+        let tag = self.keyed(route_id, id).finalize().into_bytes();
+        for byte in &tag[..TAG_LEN] {
+            let _ = write!(value, "{byte:02x}");
+        }
+        value
+    }
+
+    /// The upstream id in a cookie value, when it was signed for `route_id`.
+    pub fn verify(&self, route_id: &str, value: &str) -> Option<u64> {
+        let (id, tag) = value.split_once('.')?;
+        if id.len() != 16 || tag.len() != TAG_LEN * 2 {
+            return None;
+        }
+        let id = u64::from_str_radix(id, 16).ok()?;
+        let tag = decode_hex(tag)?;
+        self.keyed(route_id, id).verify_truncated_left(&tag).ok()?;
+        Some(id)
+    }
+
+    /// The verified upstream id in the request's sticky cookie, if any.
+    pub fn pinned(
+        &self,
+        route: &Route,
+        sticky: &StickySession,
+        request: &RequestHeader,
+    ) -> Option<u64> {
+        self.verify(&route.id, cookie_value(request, &sticky.cookie)?)
+    }
+
+    /// The MAC over `route_id` and `id`, so a cookie cannot be replayed
+    /// against another route.
+    fn keyed(&self, route_id: &str, id: u64) -> Hmac<Sha256> {
+        let mut mac = self.mac.clone();
+        mac.update(route_id.as_bytes());
+        mac.update(&[0]);
+        mac.update(&id.to_be_bytes());
+        mac
+    }
+}
+
+/// Stable id of an upstream URL in sticky cookies.
+pub fn upstream_id(url: &str) -> u64 {
+    hash_bytes(url.as_bytes())
+}
+
+/// The route's upstream with id `id`, unless it is unavailable, in
+/// `exclude` or outside the request's split `subset`; the caller then falls
+/// back to the route's `lb`.
+pub fn pinned_upstream(
+    route: &Route,
+    subset: Option<&str>,
+    exclude: &[String],
+    id: u64,
+) -> Option<Upstream> {
+    route
+        .upstreams
+        .iter()
+        .find(|upstream| upstream_id(&upstream.url) == id)
+        .filter(|upstream| {
+            upstream.status.is_available()
+                && !exclude.contains(&upstream.url)
+                && (subset.is_none() || upstream.subset.as_deref() == subset)
+        })
+        .cloned()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route() -> Route {
+        let upstreams = (0..3)
+            .map(|idx| Upstream::new(format!("http://10.0.0.{idx}:8080")))
+            .collect();
+        Route::new("legacy".to_string(), None, Vec::new(), None, upstreams)
+    }
+
+    #[test]
+    fn cookie_round_trips_only_for_its_route_and_key() {
+        let signer = CookieSigner::new(b"secret");
+        let value = signer.cookie_value("legacy", "http://10.0.0.1:8080");
+
+        assert_eq!(
+            signer.verify("legacy", &value),
+            Some(upstream_id("http://10.0.0.1:8080"))
+        );
+        assert_eq!(signer.verify("other", &value), None);
+        assert_eq!(CookieSigner::new(b"rotated").verify("legacy", &value), None);
+    }
+
+    #[test]
+    fn tampered_cookies_are_rejected() {
+        let signer = CookieSigner::new(b"secret");
+        let value = signer.cookie_value("legacy", "http://10.0.0.1:8080");
+        let (_, tag) = value.split_once('.').unwrap();
+        let forged = format!("{:016x}.{tag}", upstream_id("http://10.0.0.2:8080"));
+
+        assert_eq!(signer.verify("legacy", &forged), None);
+        assert_eq!(signer.verify("legacy", &value[..value.len() - 2]), None);
+        assert_eq!(signer.verify("legacy", "not-a-cookie"), None);
+    }
+
+    #[test]
+    fn pinned_upstream_is_used_while_available() {
+        let route = route();
+        let id = upstream_id("http://10.0.0.2:8080");
+
+        let upstream = pinned_upstream(&route, None, &[], id).unwrap();
+        assert_eq!(upstream.url, "http://10.0.0.2:8080");
+
+        let tried = vec!["http://10.0.0.2:8080".to_string()];
+        assert!(pinned_upstream(&route, None, &tried, id).is_none());
+        route.upstreams[2].status.set_healthy(false);
+        assert!(pinned_upstream(&route, None, &[], id).is_none());
+        assert!(pinned_upstream(&route, None, &[], upstream_id("http://10.0.0.9:8080")).is_none());
+    }
+
+    #[test]
+    fn pinned_upstream_outside_the_subset_is_ignored() {
+        let mut route = route();
+        route.upstreams[1].subset = Some("canary".to_string());
+        let id = upstream_id("http://10.0.0.1:8080");
+
+        assert!(pinned_upstream(&route, Some("canary"), &[], id).is_some());
+        assert!(pinned_upstream(&route, Some("stable"), &[], id).is_none());
+    }
+
+    #[test]
+    fn set_cookie_carries_max_age_when_configured() {
+        let mut sticky = StickySession {
+            cookie: "gateway_sticky".to_string(),
+            ttl: None,
+        };
+        assert_eq!(
+            sticky.set_cookie("abc"),
+            "gateway_sticky=abc; Path=/; HttpOnly; SameSite=Lax"
+        );
+        sticky.ttl = Some(Duration::from_secs(3600));
+        assert_eq!(
+            sticky.set_cookie("abc"),
+            "gateway_sticky=abc; Path=/; HttpOnly; SameSite=Lax; Max-Age=3600"
+        );
+    }
+}
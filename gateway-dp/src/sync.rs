@@ -1,30 +1,54 @@
 use async_trait::async_trait;
 use gateway_proto::config::config_service_client::ConfigServiceClient;
-use gateway_proto::config::{Snapshot, SubscribeRequest};
+use gateway_proto::config::{
+    hash_key, header_match, path_match, query_match, rewrite, split_rule, HeaderMatch, PathMatch,
+    QueryMatch, Snapshot, SubscribeRequest,
+};
 use pingora::prelude::*;
 use pingora::server::ShutdownWatch;
 use pingora::services::background::BackgroundService;
+use regex::{Regex, RegexBuilder};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
+use tonic::transport::{ClientTlsConfig, Endpoint};
 use tracing::{debug, info, warn};
 
-use crate::router::{Route, RouteSnapshot, Upstream};
+use crate::mirror::Mirror;
+use crate::ratelimit::{RateLimit, RateLimitKey, MIN_REQUESTS_PER_SECOND};
+use crate::router::{
+    CircuitBreaker, Failover, HashKey, HashTable, HeaderMatcher, HeaderOps, HeaderTemplate,
+    HealthCheck, HostMatcher, LbPolicy, OutlierDetection, PathMatcher, PathRewrite, PathTemplate,
+    QueryMatcher, RetryOn, Rewrite, Route, RouteSnapshot, SplitCondition, SplitRule, Timeouts,
+    TrafficSplit, Upstream, UpstreamTls, ValueRule,
+};
 use crate::state::State;
+use crate::sticky::StickySession;
+use crate::upstream::UpstreamRegistry;
 
 pub struct CpSync {
     endpoint: String,
+    tls: Option<ClientTlsConfig>,
     state: Arc<State>,
 }
 
 impl CpSync {
-    pub fn new(endpoint: String, state: Arc<State>) -> Self {
-        Self { endpoint, state }
+    pub fn new(endpoint: String, tls: Option<ClientTlsConfig>, state: Arc<State>) -> Self {
+        Self {
+            endpoint,
+            tls,
+            state,
+        }
     }
 
     async fn run_once(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        info!(endpoint = %self.endpoint, "connecting to control plane");
-        let mut client = ConfigServiceClient::connect(self.endpoint.clone()).await?;
+        info!(endpoint = %self.endpoint, tls = self.tls.is_some(), "connecting to control plane");
+        let mut endpoint = Endpoint::from_shared(self.endpoint.clone())?;
+        if let Some(tls) = &self.tls {
+            endpoint = endpoint.tls_config(tls.clone())?;
+        }
+        let mut client = ConfigServiceClient::new(endpoint.connect().await?);
         let mut stream = client
             .subscribe(SubscribeRequest { last_version: 0 })
             .await?
@@ -37,7 +61,8 @@ impl CpSync {
                 routes = route_count,
                 "received config snapshot"
             );
-            let new_snapshot = snapshot_to_routes(snapshot);
+            let new_snapshot =
+                snapshot_to_routes(snapshot, self.state.upstreams(), &self.state.snapshot());
             debug!(
                 routes = new_snapshot.routes.len(),
                 "applying config snapshot"
@@ -67,11 +92,23 @@ impl BackgroundService for CpSync {
     }
 }
 
-fn snapshot_to_routes(snapshot: Snapshot) -> RouteSnapshot {
+/// Converts a config snapshot. Hash tables are taken over from `previous`
+/// for routes whose upstream set did not change.
+fn snapshot_to_routes(
+    snapshot: Snapshot,
+    registry: &UpstreamRegistry,
+    previous: &RouteSnapshot,
+) -> RouteSnapshot {
+    let previous_tables: HashMap<&str, &Arc<HashTable>> = previous
+        .routes
+        .iter()
+        .filter_map(|route| Some((route.id.as_str(), route.hash_table.as_ref()?)))
+        .collect();
+    let mut urls = HashSet::new();
     let routes = snapshot
         .routes
         .into_iter()
-        .map(|route| {
+        .filter_map(|route| {
             let path_prefix = route.r#match.as_ref().and_then(|m| {
                 if m.path_prefix.is_empty() {
                     None
@@ -91,16 +128,531 @@ fn snapshot_to_routes(snapshot: Snapshot) -> RouteSnapshot {
                     Some(m.host.clone())
                 }
             });
+            let hosts = route
+                .r#match
+                .as_ref()
+                .map(|m| m.hosts.clone())
+                .unwrap_or_default();
+
+            let headers = match route.r#match.as_ref().map(|m| headers_from_proto(&m.headers)) {
+                Some(Ok(headers)) => headers,
+                None => Vec::new(),
+                Some(Err(err)) => {
+                    warn!(route_id = %route.id, error = %err, "invalid header match, skipping route");
+                    return None;
+                }
+            };
+
+            let query = match route.r#match.as_ref().map(|m| query_from_proto(&m.query)) {
+                Some(Ok(query)) => query,
+                None => Vec::new(),
+                Some(Err(err)) => {
+                    warn!(route_id = %route.id, error = %err, "invalid query match, skipping route");
+                    return None;
+                }
+            };
+
+            let path = match route.r#match.as_ref().and_then(|m| m.path.as_ref()) {
+                Some(path) => match path_from_proto(path) {
+                    Ok(path) => path,
+                    Err(err) => {
+                        warn!(route_id = %route.id, error = %err, "invalid path match, skipping route");
+                        return None;
+                    }
+                },
+                None => None,
+            };
 
             let upstreams = route
                 .upstreams
                 .into_iter()
-                .map(|u| Upstream { url: u.url })
-                .collect();
+                .map(|u| -> Result<Upstream, String> {
+                    urls.insert(u.url.clone());
+                    let tls = u
+                        .tls
+                        .map(|tls| upstream_tls_from_proto(&u.url, tls))
+                        .transpose()?;
+                    Ok(Upstream {
+                        weight: u.weight,
+                        priority: u.priority,
+                        health_check: u.health_check.and_then(health_check_from_proto),
+                        outlier_detection: u
+                            .outlier_detection
+                            .and_then(outlier_detection_from_proto),
+                        circuit_breaker: u.circuit_breaker.and_then(circuit_breaker_from_proto),
+                        tls,
+                        subset: (!u.subset.is_empty()).then_some(u.subset),
+                        status: registry.status(&u.url),
+                        ..Upstream::new(u.url)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>();
+            let upstreams = match upstreams {
+                Ok(upstreams) => upstreams,
+                Err(err) => {
+                    warn!(route_id = %route.id, error = %err, "invalid upstream tls, skipping route");
+                    return None;
+                }
+            };
+            let lb = LbPolicy::from_name(&route.lb).unwrap_or_else(|| {
+                warn!(route_id = %route.id, lb = %route.lb, "unknown lb policy, using round_robin");
+                LbPolicy::RoundRobin
+            });
+
+            let hash_key = route.hash_key.and_then(hash_key_from_proto);
+            let sticky = route.sticky_session.and_then(sticky_session_from_proto);
+            let failover = route.failover.and_then(failover_from_proto);
+
+            let request_headers = match route.request_headers.map(header_ops_from_proto) {
+                Some(Ok(ops)) => ops,
+                None => HeaderOps::default(),
+                Some(Err(err)) => {
+                    warn!(route_id = %route.id, error = %err, "invalid request headers, skipping route");
+                    return None;
+                }
+            };
+            let response_headers = match route.response_headers.map(header_ops_from_proto) {
+                Some(Ok(ops)) => ops,
+                None => HeaderOps::default(),
+                Some(Err(err)) => {
+                    warn!(route_id = %route.id, error = %err, "invalid response headers, skipping route");
+                    return None;
+                }
+            };
+
+            let rate_limit = match route.rate_limit.map(rate_limit_from_proto).transpose() {
+                Ok(rate_limit) => rate_limit,
+                Err(err) => {
+                    warn!(route_id = %route.id, error = %err, "invalid rate limit, skipping route");
+                    return None;
+                }
+            };
+            let mirror = match route.mirror.map(mirror_from_proto).transpose() {
+                Ok(mirror) => mirror,
+                Err(err) => {
+                    warn!(route_id = %route.id, error = %err, "invalid mirror, skipping route");
+                    return None;
+                }
+            };
+            let split = match route.split.map(split_from_proto).transpose() {
+                Ok(split) => split,
+                Err(err) => {
+                    warn!(route_id = %route.id, error = %err, "invalid traffic split, skipping route");
+                    return None;
+                }
+            };
+            let timeouts = route.timeouts.map(timeouts_from_proto).unwrap_or_default();
+            let max_body_bytes = route
+                .limits
+                .map(|limits| limits.max_body_bytes)
+                .filter(|max| *max > 0);
+
+            let rewrite = match route.rewrite.map(rewrite_from_proto).transpose() {
+                Ok(rewrite) => rewrite,
+                Err(err) => {
+                    warn!(route_id = %route.id, error = %err, "invalid rewrite, skipping route");
+                    return None;
+                }
+            };
 
-            Route::new(route.id, path_prefix, methods, host, upstreams)
+            let mut route = Route::new(route.id, path_prefix, methods, host, upstreams);
+            if path.is_some() {
+                route.path = path;
+            }
+            route
+                .hosts
+                .extend(hosts.iter().map(|host| HostMatcher::parse(host)));
+            route.headers = headers;
+            route.query = query;
+            route.lb = lb;
+            route.hash_key = hash_key;
+            route.hash_table = match previous_tables.get(route.id.as_str()) {
+                Some(table) if table.built_for(lb, &route.upstreams) => Some(Arc::clone(table)),
+                _ => HashTable::build(lb, &route.upstreams).map(Arc::new),
+            };
+            route.sticky = sticky;
+            route.failover = failover;
+            route.rewrite = rewrite;
+            route.request_headers = request_headers;
+            route.response_headers = response_headers;
+            route.max_body_bytes = max_body_bytes;
+            route.timeouts = timeouts;
+            route.rate_limit = rate_limit;
+            route.mirror = mirror;
+            route.split = split;
+            Some(route)
         })
         .collect();
+    registry.retain(&urls);
+
+    RouteSnapshot::new(routes)
+}
+
+/// Converts header rules. Patterns are validated by the control plane, so a
+/// regex that still fails to compile is reported as an error for the route.
+fn headers_from_proto(headers: &[HeaderMatch]) -> Result<Vec<HeaderMatcher>, regex::Error> {
+    headers
+        .iter()
+        .map(|header| {
+            let fold = |value: &str| {
+                if header.ignore_case {
+                    value.to_lowercase()
+                } else {
+                    value.to_string()
+                }
+            };
+            let rule = match &header.rule {
+                Some(header_match::Rule::Exact(value)) => ValueRule::Exact(fold(value)),
+                Some(header_match::Rule::Prefix(value)) => ValueRule::Prefix(fold(value)),
+                Some(header_match::Rule::Regex(pattern)) => ValueRule::Regex(
+                    RegexBuilder::new(pattern)
+                        .case_insensitive(header.ignore_case)
+                        .build()?,
+                ),
+                Some(header_match::Rule::Present(true)) | None => ValueRule::Present,
+                Some(header_match::Rule::Present(false)) => ValueRule::Absent,
+            };
+            Ok(HeaderMatcher {
+                name: header.name.to_ascii_lowercase(),
+                rule,
+                ignore_case: header.ignore_case,
+            })
+        })
+        .collect()
+}
+
+fn path_from_proto(path: &PathMatch) -> Result<Option<PathMatcher>, String> {
+    Ok(match &path.kind {
+        Some(path_match::Kind::Exact(value)) => Some(PathMatcher::Exact(value.clone())),
+        Some(path_match::Kind::Prefix(value)) => Some(PathMatcher::Prefix(value.clone())),
+        Some(path_match::Kind::Regex(pattern)) => Some(PathMatcher::Regex(
+            Regex::new(pattern).map_err(|err| err.to_string())?,
+        )),
+        Some(path_match::Kind::Template(template)) => {
+            Some(PathMatcher::Template(PathTemplate::parse(template)?))
+        }
+        None => None,
+    })
+}
+
+fn query_from_proto(params: &[QueryMatch]) -> Result<Vec<QueryMatcher>, regex::Error> {
+    params
+        .iter()
+        .map(|param| {
+            let rule = match &param.rule {
+                Some(query_match::Rule::Exact(value)) => ValueRule::Exact(value.clone()),
+                Some(query_match::Rule::Regex(pattern)) => ValueRule::Regex(Regex::new(pattern)?),
+                Some(query_match::Rule::Present(true)) | None => ValueRule::Present,
+                Some(query_match::Rule::Present(false)) => ValueRule::Absent,
+            };
+            Ok(QueryMatcher {
+                name: param.name.clone(),
+                rule,
+            })
+        })
+        .collect()
+}
+
+fn rewrite_from_proto(rewrite: gateway_proto::config::Rewrite) -> Result<Rewrite, regex::Error> {
+    let path = match rewrite.path {
+        Some(rewrite::Path::StripPrefix(prefix)) => Some(PathRewrite::StripPrefix(prefix)),
+        Some(rewrite::Path::ReplacePrefix(replace)) => Some(PathRewrite::ReplacePrefix {
+            prefix: replace.prefix,
+            replacement: replace.replacement,
+        }),
+        Some(rewrite::Path::Regex(regex)) => Some(PathRewrite::Regex {
+            pattern: Regex::new(&regex.pattern)?,
+            substitution: regex.substitution,
+        }),
+        None => None,
+    };
+    let host = (!rewrite.host.is_empty()).then_some(rewrite.host);
+    Ok(Rewrite { path, host })
+}
+
+fn header_ops_from_proto(ops: gateway_proto::config::HeaderOps) -> Result<HeaderOps, String> {
+    let values = |values: Vec<gateway_proto::config::HeaderValue>| {
+        values
+            .into_iter()
+            .map(|header| Ok((header.name, HeaderTemplate::parse(&header.value)?)))
+            .collect::<Result<Vec<_>, String>>()
+    };
+    Ok(HeaderOps {
+        add: values(ops.add)?,
+        set: values(ops.set)?,
+        remove: ops.remove,
+    })
+}
+
+fn hash_key_from_proto(key: gateway_proto::config::HashKey) -> Option<HashKey> {
+    match key.source? {
+        hash_key::Source::Header(name) => Some(HashKey::Header(name.to_lowercase())),
+        hash_key::Source::Cookie(name) => Some(HashKey::Cookie(name)),
+        hash_key::Source::ClientIp(true) => Some(HashKey::ClientIp),
+        hash_key::Source::ClientIp(false) => None,
+        hash_key::Source::PathSegment(index) => Some(HashKey::PathSegment(index as usize)),
+    }
+}
+
+fn sticky_session_from_proto(
+    sticky: gateway_proto::config::StickySession,
+) -> Option<StickySession> {
+    if sticky.cookie.is_empty() {
+        return None;
+    }
+    Some(StickySession {
+        cookie: sticky.cookie,
+        ttl: (sticky.ttl_ms > 0).then(|| Duration::from_millis(sticky.ttl_ms)),
+    })
+}
+
+fn mirror_from_proto(mirror: gateway_proto::config::Mirror) -> Result<Mirror, String> {
+    if mirror.url.is_empty() {
+        return Err("url must be set".to_string());
+    }
+    if !(mirror.percent > 0.0 && mirror.percent <= 100.0) {
+        return Err("percent must be greater than 0 and at most 100".to_string());
+    }
+    Ok(Mirror {
+        upstream: Upstream::new(mirror.url),
+        percent: mirror.percent,
+        max_body_bytes: mirror.max_body_bytes,
+    })
+}
+
+fn split_from_proto(split: gateway_proto::config::TrafficSplit) -> Result<TrafficSplit, String> {
+    if split.default_subset.is_empty() {
+        return Err("default_subset must be set".to_string());
+    }
+    let rules = split
+        .rules
+        .into_iter()
+        .enumerate()
+        .map(|(index, rule)| {
+            let value = |value: String| (!value.is_empty()).then_some(value);
+            let condition = match rule.condition {
+                Some(split_rule::Condition::Header(m)) if !m.name.is_empty() => {
+                    SplitCondition::Header {
+                        name: m.name.to_lowercase(),
+                        value: value(m.value),
+                    }
+                }
+                Some(split_rule::Condition::Cookie(m)) if !m.name.is_empty() => {
+                    SplitCondition::Cookie {
+                        name: m.name,
+                        value: value(m.value),
+                    }
+                }
+                Some(split_rule::Condition::Hash(bucket)) => SplitCondition::HashBucket {
+                    key: bucket
+                        .key
+                        .and_then(hash_key_from_proto)
+                        .ok_or_else(|| format!("rules[{index}] has an invalid hash key"))?,
+                    percent: bucket.percent,
+                },
+                _ => return Err(format!("rules[{index}] has no valid condition")),
+            };
+            Ok(SplitRule {
+                subset: rule.subset,
+                condition,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(TrafficSplit {
+        rules,
+        default_subset: split.default_subset,
+    })
+}
+
+fn rate_limit_from_proto(limit: gateway_proto::config::RateLimit) -> Result<RateLimit, String> {
+    if !(limit.requests_per_second.is_finite()
+        && limit.requests_per_second >= MIN_REQUESTS_PER_SECOND)
+    {
+        return Err("requests_per_second must allow at least one request per day".to_string());
+    }
+    if limit.burst == 0 {
+        return Err("burst must be greater than 0".to_string());
+    }
+    let key = match limit.key.as_str() {
+        "" | "client_ip" => RateLimitKey::ClientIp,
+        "header" if !limit.header.is_empty() => RateLimitKey::Header(limit.header.to_lowercase()),
+        "route" => RateLimitKey::Route,
+        other => return Err(format!("unsupported key {other:?}")),
+    };
+    Ok(RateLimit {
+        requests_per_second: limit.requests_per_second,
+        burst: limit.burst,
+        key,
+    })
+}
+
+fn timeouts_from_proto(timeouts: gateway_proto::config::Timeouts) -> Timeouts {
+    let millis = |ms: u64| (ms > 0).then(|| Duration::from_millis(ms));
+    Timeouts {
+        connect: millis(timeouts.connect_ms),
+        read: millis(timeouts.read_ms),
+        write: millis(timeouts.write_ms),
+        idle: millis(timeouts.idle_ms),
+        total: millis(timeouts.total_ms),
+    }
+}
+
+fn health_check_from_proto(check: gateway_proto::config::HealthCheck) -> Option<HealthCheck> {
+    if check.interval_ms == 0 {
+        return None;
+    }
+    Some(HealthCheck {
+        path: if check.path.is_empty() {
+            "/".to_string()
+        } else {
+            check.path
+        },
+        interval: Duration::from_millis(check.interval_ms),
+        timeout: Duration::from_millis(check.timeout_ms.max(1)),
+        unhealthy_threshold: check.unhealthy_threshold.max(1),
+        healthy_threshold: check.healthy_threshold.max(1),
+    })
+}
+
+fn outlier_detection_from_proto(
+    outlier: gateway_proto::config::OutlierDetection,
+) -> Option<OutlierDetection> {
+    if outlier.consecutive_5xx == 0 || outlier.eject_ms == 0 {
+        return None;
+    }
+    Some(OutlierDetection {
+        consecutive_5xx: outlier.consecutive_5xx,
+        eject: Duration::from_millis(outlier.eject_ms),
+    })
+}
 
-    RouteSnapshot { routes }
+fn circuit_breaker_from_proto(
+    breaker: gateway_proto::config::CircuitBreaker,
+) -> Option<CircuitBreaker> {
+    if breaker.window_ms == 0
+        || breaker.cooldown_ms == 0
+        || (breaker.error_rate_percent == 0 && breaker.latency_ms == 0)
+    {
+        return None;
+    }
+    Some(CircuitBreaker {
+        window: Duration::from_millis(breaker.window_ms),
+        min_requests: breaker.min_requests.max(1),
+        error_rate_percent: breaker.error_rate_percent.min(100),
+        latency: (breaker.latency_ms > 0).then(|| Duration::from_millis(breaker.latency_ms)),
+        cooldown: Duration::from_millis(breaker.cooldown_ms),
+        half_open_probes: breaker.half_open_probes.max(1),
+    })
+}
+
+fn upstream_tls_from_proto(
+    url: &str,
+    tls: gateway_proto::config::TlsOverride,
+) -> Result<UpstreamTls, String> {
+    if tls.insecure_skip_verify {
+        warn!(upstream = %url, "upstream certificate verification is disabled");
+    }
+    let ca = match tls.ca_cert_path.as_str() {
+        "" => None,
+        path => Some(Arc::new(crate::tls::upstream_ca(path)?)),
+    };
+    Ok(UpstreamTls {
+        server_name: (!tls.server_name.is_empty()).then_some(tls.server_name),
+        ca,
+        insecure_skip_verify: tls.insecure_skip_verify,
+    })
+}
+
+fn failover_from_proto(failover: gateway_proto::config::Failover) -> Option<Failover> {
+    if !failover.enabled || failover.max_failovers == 0 {
+        return None;
+    }
+    let mut retry_on = RetryOn::default();
+    for value in &failover.retry_on {
+        match value.as_str() {
+            "connect_failure" => retry_on.connect_failure = true,
+            "5xx" => retry_on.http_5xx = true,
+            "timeout" => retry_on.timeout = true,
+            other => warn!(retry_on = %other, "ignoring unknown failover retry_on value"),
+        }
+    }
+    Some(Failover {
+        max_failovers: failover.max_failovers,
+        retry_on,
+        per_try_timeout: (failover.per_try_timeout_ms > 0)
+            .then(|| Duration::from_millis(failover.per_try_timeout_ms)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(lb: &str, upstreams: &[(&str, u32)]) -> Snapshot {
+        Snapshot {
+            version: 1,
+            routes: vec![gateway_proto::config::Route {
+                id: "orders".to_string(),
+                lb: lb.to_string(),
+                upstreams: upstreams
+                    .iter()
+                    .map(|(url, weight)| gateway_proto::config::Upstream {
+                        url: url.to_string(),
+                        weight: *weight,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn table(snapshot: &RouteSnapshot) -> &Arc<HashTable> {
+        snapshot.routes[0].hash_table.as_ref().unwrap()
+    }
+
+    #[test]
+    fn hash_tables_are_kept_while_the_upstream_set_holds() {
+        let registry = UpstreamRegistry::new();
+        let upstreams = [("http://10.0.0.1:8080", 1), ("http://10.0.0.2:8080", 2)];
+        for lb in ["ring_hash", "maglev"] {
+            let first =
+                snapshot_to_routes(snapshot(lb, &upstreams), &registry, &RouteSnapshot::empty());
+            let second = snapshot_to_routes(snapshot(lb, &upstreams), &registry, &first);
+
+            assert!(Arc::ptr_eq(table(&first), table(&second)));
+        }
+    }
+
+    #[test]
+    fn hash_tables_are_rebuilt_when_upstreams_or_weights_change() {
+        let registry = UpstreamRegistry::new();
+        let upstreams = [("http://10.0.0.1:8080", 1), ("http://10.0.0.2:8080", 2)];
+        for lb in ["ring_hash", "maglev"] {
+            let first =
+                snapshot_to_routes(snapshot(lb, &upstreams), &registry, &RouteSnapshot::empty());
+            for changed in [
+                snapshot(lb, &[("http://10.0.0.1:8080", 1)]),
+                snapshot(
+                    lb,
+                    &[("http://10.0.0.1:8080", 1), ("http://10.0.0.3:8080", 2)],
+                ),
+                snapshot(
+                    lb,
+                    &[("http://10.0.0.1:8080", 1), ("http://10.0.0.2:8080", 5)],
+                ),
+            ] {
+                let second = snapshot_to_routes(changed, &registry, &first);
+                assert!(!Arc::ptr_eq(table(&first), table(&second)));
+            }
+        }
+
+        let ring = snapshot_to_routes(
+            snapshot("ring_hash", &upstreams),
+            &registry,
+            &RouteSnapshot::empty(),
+        );
+        let maglev = snapshot_to_routes(snapshot("maglev", &upstreams), &registry, &ring);
+        assert!(!Arc::ptr_eq(table(&ring), table(&maglev)));
+    }
 }
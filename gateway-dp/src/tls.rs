@@ -0,0 +1,216 @@
+use pingora::listeners::tls::TlsSettings;
+use pingora::protocols::tls::CaType;
+use pingora::tls::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod};
+use pingora::tls::x509::X509;
+use std::fs;
+use tonic::transport::{Certificate, ClientTlsConfig, Identity};
+
+use crate::config::{ControlPlaneTlsConfig, TlsConfig};
+
+/// Builds the TLS settings for the downstream listener. ALPN offers h2 and
+/// falls back to http/1.1.
+pub fn listener_settings(tls: &TlsConfig) -> Result<TlsSettings, String> {
+    let mut settings = TlsSettings::from(acceptor_builder(tls)?);
+    settings.enable_h2();
+    Ok(settings)
+}
+
+/// Mozilla intermediate settings with the listener certificate chain and
+/// key. Both are loaded up front, and the key must match the certificate,
+/// so a bad file fails startup with a message naming it instead of
+/// surfacing later as a handshake error.
+fn acceptor_builder(tls: &TlsConfig) -> Result<SslAcceptorBuilder, String> {
+    check_readable("cert_path", &tls.cert_path)?;
+    check_readable("key_path", &tls.key_path)?;
+
+    let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls())
+        .map_err(|err| format!("cannot create TLS acceptor: {err}"))?;
+    builder
+        .set_certificate_chain_file(&tls.cert_path)
+        .map_err(|err| format!("invalid listener.tls.cert_path {}: {err}", tls.cert_path))?;
+    builder
+        .set_private_key_file(&tls.key_path, SslFiletype::PEM)
+        .map_err(|err| format!("invalid listener.tls.key_path {}: {err}", tls.key_path))?;
+    Ok(builder)
+}
+
+/// Builds the client TLS config for the control plane channel. Only the
+/// configured CA is trusted, and a client identity is attached when both
+/// certificate and key are given.
+pub fn control_plane_client(tls: &ControlPlaneTlsConfig) -> Result<ClientTlsConfig, String> {
+    let ca = read_pem("control_plane.tls.ca_path", &tls.ca_path)?;
+    let mut config = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(ca));
+
+    match (&tls.cert_path, &tls.key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let cert = read_pem("control_plane.tls.cert_path", cert_path)?;
+            let key = read_pem("control_plane.tls.key_path", key_path)?;
+            config = config.identity(Identity::from_pem(cert, key));
+        }
+        (None, None) => {}
+        _ => {
+            return Err("control_plane.tls.cert_path and key_path must be set together".to_string())
+        }
+    }
+
+    if let Some(name) = &tls.server_name {
+        config = config.domain_name(name.clone());
+    }
+    Ok(config)
+}
+
+/// Loads the PEM bundle of an upstream's `ca_cert_path`, which replaces the
+/// system trust store when verifying that upstream.
+pub fn upstream_ca(path: &str) -> Result<CaType, String> {
+    let pem = read_pem("tls.ca_cert_path", path)?;
+    let certs = X509::stack_from_pem(&pem)
+        .map_err(|err| format!("invalid tls.ca_cert_path {path}: {err}"))?;
+    if certs.is_empty() {
+        return Err(format!("tls.ca_cert_path {path} holds no certificates"));
+    }
+    Ok(certs.into_boxed_slice())
+}
+
+fn check_readable(field: &str, path: &str) -> Result<(), String> {
+    read_pem(&format!("listener.tls.{field}"), path).map(|_| ())
+}
+
+fn read_pem(field: &str, path: &str) -> Result<Vec<u8>, String> {
+    match fs::read(path) {
+        Ok(contents) if contents.is_empty() => Err(format!("{field} {path} is empty")),
+        Ok(contents) => Ok(contents),
+        Err(err) => Err(format!("cannot read {field} {path}: {err}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::ssl::SslConnector;
+    use openssl::x509::{X509NameBuilder, X509};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::path::PathBuf;
+
+    /// A self-signed certificate for `localhost`, written to temp files.
+    fn self_signed(name: &str) -> (X509, PathBuf, PathBuf) {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", "localhost").unwrap();
+        let subject = subject.build();
+
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&subject).unwrap();
+        cert.set_issuer_name(&subject).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap())
+            .unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        let cert = cert.build();
+
+        let dir = std::env::temp_dir();
+        let cert_path = dir.join(format!("gateway-dp-{name}-{}.crt", std::process::id()));
+        let key_path = dir.join(format!("gateway-dp-{name}-{}.key", std::process::id()));
+        fs::write(&cert_path, cert.to_pem().unwrap()).unwrap();
+        fs::write(&key_path, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        (cert, cert_path, key_path)
+    }
+
+    #[test]
+    fn listener_completes_a_handshake_with_its_certificate() {
+        let (cert, cert_path, key_path) = self_signed("listener");
+        let tls = TlsConfig {
+            cert_path: cert_path.display().to_string(),
+            key_path: key_path.display().to_string(),
+        };
+        let acceptor = acceptor_builder(&tls).unwrap().build();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut stream = acceptor.accept(stream).unwrap();
+            stream.write_all(b"hello").unwrap();
+            stream.shutdown().unwrap();
+        });
+
+        let mut connector = SslConnector::builder(openssl::ssl::SslMethod::tls()).unwrap();
+        connector.cert_store_mut().add_cert(cert).unwrap();
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut stream = connector.build().connect("localhost", stream).unwrap();
+        let mut body = String::new();
+        stream.read_to_string(&mut body).unwrap();
+        server.join().unwrap();
+
+        assert_eq!(body, "hello");
+        let _ = fs::remove_file(cert_path);
+        let _ = fs::remove_file(key_path);
+    }
+
+    #[test]
+    fn key_must_match_the_certificate() {
+        let (_, cert_path, _) = self_signed("cert");
+        let (_, other_cert, other_key) = self_signed("other");
+        let tls = TlsConfig {
+            cert_path: cert_path.display().to_string(),
+            key_path: other_key.display().to_string(),
+        };
+
+        let err = acceptor_builder(&tls).err().unwrap();
+        assert!(err.contains("listener.tls.key_path"), "{err}");
+        for path in [cert_path, other_cert, other_key] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn upstream_ca_loads_every_certificate_in_the_bundle() {
+        let (first, first_path, first_key) = self_signed("ca-first");
+        let (second, second_path, second_key) = self_signed("ca-second");
+        let mut bundle = first.to_pem().unwrap();
+        bundle.extend(second.to_pem().unwrap());
+        fs::write(&first_path, bundle).unwrap();
+
+        let ca = upstream_ca(&first_path.display().to_string()).unwrap();
+        assert_eq!(ca.len(), 2);
+
+        fs::write(&second_path, "not a certificate").unwrap();
+        let err = upstream_ca(&second_path.display().to_string()).unwrap_err();
+        assert!(err.contains("tls.ca_cert_path"), "{err}");
+
+        for path in [first_path, first_key, second_path, second_key] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn control_plane_identity_needs_cert_and_key() {
+        let ca = std::env::temp_dir().join(format!("gateway-dp-cp-ca-{}.pem", std::process::id()));
+        fs::write(&ca, "-----BEGIN CERTIFICATE-----\n").unwrap();
+
+        let tls = ControlPlaneTlsConfig {
+            ca_path: ca.display().to_string(),
+            cert_path: Some(ca.display().to_string()),
+            key_path: None,
+            server_name: None,
+        };
+        let err = control_plane_client(&tls).err().unwrap();
+        assert!(err.contains("must be set together"), "{err}");
+
+        let pinned_only = ControlPlaneTlsConfig {
+            cert_path: None,
+            ..tls
+        };
+        assert!(control_plane_client(&pinned_only).is_ok());
+
+        let _ = fs::remove_file(ca);
+    }
+}
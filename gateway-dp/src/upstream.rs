@@ -0,0 +1,268 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::circuit::Circuit;
+
+/// Latency assumed for an upstream before its first response, so new
+/// upstreams are not flooded by `peak_ewma`.
+const DEFAULT_RTT: Duration = Duration::from_millis(30);
+/// Decay time of the `peak_ewma` latency average.
+const EWMA_DECAY: Duration = Duration::from_secs(10);
+
+/// Runtime status of a single upstream, shared by every route that points at
+/// the same URL. Entries live in the [`UpstreamRegistry`] rather than in the
+/// route snapshot so they survive config swaps.
+#[derive(Debug)]
+pub struct UpstreamStatus {
+    healthy: AtomicBool,
+    ejected_until_ms: AtomicU64,
+    consecutive_failures: AtomicU32,
+    circuit: Circuit,
+    /// Requests sent to the upstream that have not finished yet.
+    in_flight: AtomicU64,
+    rtt: Mutex<PeakEwma>,
+}
+
+/// Exponentially weighted moving average of response latency that jumps
+/// straight to any higher sample and decays back over [`EWMA_DECAY`].
+#[derive(Debug)]
+struct PeakEwma {
+    nanos: f64,
+    updated: Option<Instant>,
+}
+
+impl Default for UpstreamStatus {
+    fn default() -> Self {
+        Self {
+            healthy: AtomicBool::new(true),
+            ejected_until_ms: AtomicU64::new(0),
+            consecutive_failures: AtomicU32::new(0),
+            circuit: Circuit::default(),
+            in_flight: AtomicU64::new(0),
+            rtt: Mutex::new(PeakEwma {
+                nanos: DEFAULT_RTT.as_nanos() as f64,
+                updated: None,
+            }),
+        }
+    }
+}
+
+impl UpstreamStatus {
+    pub fn is_available(&self) -> bool {
+        self.is_healthy() && !self.is_ejected() && self.circuit.allows()
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` when the call changed the stored value.
+    pub fn set_healthy(&self, healthy: bool) -> bool {
+        self.healthy.swap(healthy, Ordering::Relaxed) != healthy
+    }
+
+    pub fn is_ejected(&self) -> bool {
+        self.ejected_until_ms.load(Ordering::Relaxed) > clock_ms()
+    }
+
+    pub fn eject_for(&self, duration: Duration) {
+        let until = clock_ms().saturating_add(duration.as_millis() as u64);
+        self.ejected_until_ms.store(until, Ordering::Relaxed);
+    }
+
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns the number of consecutive failures including this one.
+    pub fn record_failure(&self) -> u32 {
+        self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn circuit(&self) -> &Circuit {
+        &self.circuit
+    }
+
+    pub fn in_flight(&self) -> u64 {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn start_request(&self) {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn finish_request(&self) {
+        // Saturate rather than wrap if a request is ever finished twice.
+        let _ = self
+            .in_flight
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+
+    pub fn record_rtt(&self, rtt: Duration) {
+        self.record_rtt_at(rtt, Instant::now());
+    }
+
+    /// `peak_ewma` cost: the latency average scaled by the requests that
+    /// would be queued behind a new one.
+    pub fn peak_ewma_cost(&self) -> f64 {
+        self.peak_ewma_cost_at(Instant::now())
+    }
+
+    fn record_rtt_at(&self, rtt: Duration, now: Instant) {
+        let mut ewma = self.rtt.lock().unwrap_or_else(|p| p.into_inner());
+        let sample = rtt.as_nanos() as f64;
+        ewma.nanos = if sample > ewma.nanos {
+            sample
+        } else {
+            let w = ewma.weight(now);
+            ewma.nanos * w + sample * (1.0 - w)
+        };
+        ewma.updated = Some(now);
+    }
+
+    fn peak_ewma_cost_at(&self, now: Instant) -> f64 {
+        let ewma = self.rtt.lock().unwrap_or_else(|p| p.into_inner());
+        ewma.decayed(now) * (self.in_flight() + 1) as f64
+    }
+}
+
+impl PeakEwma {
+    /// Weight of the current average after the time since the last sample.
+    fn weight(&self, now: Instant) -> f64 {
+        let elapsed = self
+            .updated
+            .map(|updated| now.saturating_duration_since(updated))
+            .unwrap_or_default();
+        (-elapsed.as_secs_f64() / EWMA_DECAY.as_secs_f64()).exp()
+    }
+
+    /// The average as of `now`. Idle upstreams drift back towards the
+    /// default so a single slow response does not shun them forever.
+    fn decayed(&self, now: Instant) -> f64 {
+        let default = DEFAULT_RTT.as_nanos() as f64;
+        let w = self.weight(now);
+        self.nanos * w + default.min(self.nanos) * (1.0 - w)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct UpstreamRegistry {
+    entries: Mutex<HashMap<String, Arc<UpstreamStatus>>>,
+}
+
+impl UpstreamRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the status for `url`, creating a fresh one on first use.
+    pub fn status(&self, url: &str) -> Arc<UpstreamStatus> {
+        let mut entries = self.lock();
+        entries.entry(url.to_string()).or_default().clone()
+    }
+
+    /// Every known upstream and its status, ordered by URL.
+    pub fn entries(&self) -> Vec<(String, Arc<UpstreamStatus>)> {
+        let mut entries: Vec<_> = self
+            .lock()
+            .iter()
+            .map(|(url, status)| (url.clone(), Arc::clone(status)))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Drops statuses for upstreams that are no longer referenced by any route.
+    pub fn retain(&self, urls: &HashSet<String>) {
+        self.lock().retain(|url, _| urls.contains(url));
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<UpstreamStatus>>> {
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Milliseconds on a monotonic clock local to this process.
+fn clock_ms() -> u64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_is_shared_per_url() {
+        let registry = UpstreamRegistry::new();
+        let first = registry.status("http://10.0.0.1:8080");
+        first.set_healthy(false);
+
+        let again = registry.status("http://10.0.0.1:8080");
+        assert!(!again.is_available());
+        assert!(registry.status("http://10.0.0.2:8080").is_available());
+    }
+
+    #[test]
+    fn retain_drops_unreferenced_upstreams() {
+        let registry = UpstreamRegistry::new();
+        registry.status("http://10.0.0.1:8080").set_healthy(false);
+        registry.status("http://10.0.0.2:8080").set_healthy(false);
+
+        let keep = HashSet::from(["http://10.0.0.2:8080".to_string()]);
+        registry.retain(&keep);
+
+        assert!(registry.status("http://10.0.0.1:8080").is_available());
+        assert!(!registry.status("http://10.0.0.2:8080").is_available());
+    }
+
+    #[test]
+    fn ejection_expires() {
+        let status = UpstreamStatus::default();
+        status.eject_for(Duration::from_millis(20));
+        assert!(status.is_ejected());
+        assert!(!status.is_available());
+
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(status.is_available());
+    }
+
+    #[test]
+    fn in_flight_count_is_shared_and_never_wraps() {
+        let registry = UpstreamRegistry::new();
+        registry.status("http://10.0.0.1:8080").start_request();
+        registry.status("http://10.0.0.1:8080").start_request();
+
+        let status = registry.status("http://10.0.0.1:8080");
+        assert_eq!(status.in_flight(), 2);
+        for _ in 0..3 {
+            status.finish_request();
+        }
+        assert_eq!(status.in_flight(), 0);
+    }
+
+    #[test]
+    fn peak_ewma_jumps_to_peaks_and_decays() {
+        let status = UpstreamStatus::default();
+        let start = Instant::now();
+        let default = DEFAULT_RTT.as_nanos() as f64;
+        assert_eq!(status.peak_ewma_cost_at(start), default);
+
+        status.record_rtt_at(Duration::from_millis(300), start);
+        assert_eq!(status.peak_ewma_cost_at(start), 300_000_000.0);
+        status.start_request();
+        assert_eq!(status.peak_ewma_cost_at(start), 600_000_000.0);
+        status.finish_request();
+
+        // A fast sample right after the peak barely moves the average.
+        status.record_rtt_at(Duration::from_millis(10), start);
+        assert!(status.peak_ewma_cost_at(start) > 250_000_000.0);
+
+        let idle = start + EWMA_DECAY * 10;
+        assert!(status.peak_ewma_cost_at(idle) < default * 1.01);
+    }
+}
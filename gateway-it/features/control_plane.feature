@@ -34,3 +34,243 @@ Feature: Control plane routes and policies
       """
       { "id": "users" }
       """
+
+  Scenario: Reject header rules with an unknown variable
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "headers-invalid",
+        "match": { "path_prefix": "/v1/headers" },
+        "request_headers": {
+          "set": [{ "name": "x-user", "value": "${user_id}" }]
+        },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Reject header rules naming a variable the path template lacks
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "headers-path-invalid",
+        "match": { "path": { "template": "/v1/users/{id}" } },
+        "request_headers": {
+          "set": [{ "name": "x-user", "value": "${path.user_id}" }]
+        },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Reject a read timeout longer than the total timeout
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "timeouts-invalid",
+        "match": { "path_prefix": "/v1/timeouts" },
+        "timeouts": { "read_ms": 5000, "total_ms": 1000 },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Reject a header rate limit without a header name
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "rate-limit-invalid",
+        "match": { "path_prefix": "/v1/limited" },
+        "rate_limit": { "requests_per_second": 10, "burst": 20, "key": "header" },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Reject a rate limit slower than one request per day
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "rate-limit-too-slow",
+        "match": { "path_prefix": "/v1/limited" },
+        "rate_limit": { "requests_per_second": 1e-20, "burst": 1 },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Reject a circuit breaker without a trip condition
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "circuit-breaker-invalid",
+        "match": { "path_prefix": "/v1/breaker" },
+        "upstreams": [
+          {
+            "url": "http://10.0.0.12:8080",
+            "circuit_breaker": { "window_ms": 10000, "cooldown_ms": 5000 }
+          }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Reject consistent hashing without a hash key
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "ring-hash-invalid",
+        "match": { "path_prefix": "/v1/cache" },
+        "lb": "ring_hash",
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" },
+          { "url": "http://10.0.0.13:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Create a route with latency-aware load balancing
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "search",
+        "match": { "path_prefix": "/v1/search" },
+        "lb": "peak_ewma",
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080", "weight": 2 },
+          { "url": "http://10.0.0.13:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 201
+    When I GET "/routes" on the control plane
+    Then the JSON response should include:
+      """
+      { "id": "search", "lb": "peak_ewma" }
+      """
+
+  Scenario: Reject a sticky session cookie with an invalid name
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "sticky-invalid",
+        "match": { "path_prefix": "/legacy" },
+        "sticky_session": { "cookie": "session id", "ttl_ms": 3600000 },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" },
+          { "url": "http://10.0.0.13:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Reject a mirror that targets one of the route's upstreams
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "orders-mirror-invalid",
+        "match": { "path_prefix": "/v1/orders" },
+        "mirror": { "url": "http://10.0.0.12:8080", "percent": 10 },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Reject a traffic split that names an unknown upstream subset
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "checkout-canary",
+        "match": { "path_prefix": "/checkout" },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080", "subset": "stable" },
+          { "url": "http://10.0.0.13:8080", "subset": "canary" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 201
+    When I PUT "/routes/checkout-canary/split" on the control plane with JSON:
+      """
+      {
+        "rules": [
+          { "subset": "beta", "header": { "name": "x-canary", "value": "true" } }
+        ],
+        "default_subset": "stable"
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
@@ -20,3 +20,41 @@ Feature: Control plane to data plane routing
     When I GET "/v1/dp-users" on the gateway
     Then the response status should be 200
     And the response text should be "upstream-ok"
+
+  Scenario: An unmatched request gets a JSON 404 from the gateway
+    Given the control plane is running
+    And the gateway is running
+    When I GET "/v1/no-such-route" on the gateway
+    Then the response status should be 404
+    And the JSON response should include:
+      """
+      { "error": "route_not_found" }
+      """
+
+  Scenario: A body over the route limit is rejected with 413
+    Given the control plane is running
+    And an upstream service is running
+    And the gateway is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "small-bodies",
+        "match": { "path_prefix": "/v1/small-bodies" },
+        "limits": { "max_body_bytes": 16 },
+        "upstreams": [
+          { "url": "{{upstream_url}}" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 201
+    When I wait for the route "/v1/small-bodies" to be available
+    When I POST "/v1/small-bodies" on the gateway with JSON:
+      """
+      { "payload": "this body is longer than sixteen bytes" }
+      """
+    Then the response status should be 413
+    And the JSON response should include:
+      """
+      { "error": "payload_too_large" }
+      """
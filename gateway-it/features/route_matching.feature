@@ -65,3 +65,70 @@ Feature: Deterministic route matching
     When I GET "/deterministic/method" on the gateway
     Then the response status should be 200
     And the response text should be "upstream-ok"
+
+  Scenario: Reject header match with more than one rule
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "det-header-invalid",
+        "match": {
+          "path_prefix": "/deterministic/headers",
+          "headers": [{ "name": "x-api-version", "exact": "2", "prefix": "2" }]
+        },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Reject host pattern with an inner wildcard
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "det-host-invalid",
+        "match": {
+          "path_prefix": "/deterministic/hosts",
+          "hosts": ["api.*.example.com"]
+        },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
+
+  Scenario: Reject rewrite with more than one path rule
+    Given the control plane is running
+    When I POST "/routes" on the control plane with JSON:
+      """
+      {
+        "id": "det-rewrite-invalid",
+        "match": { "path_prefix": "/deterministic/rewrite" },
+        "rewrite": {
+          "strip_prefix": "/deterministic",
+          "regex": { "pattern": "^/deterministic(.*)$", "substitution": "$1" }
+        },
+        "upstreams": [
+          { "url": "http://10.0.0.12:8080" }
+        ],
+        "policies": []
+      }
+      """
+    Then the response status should be 422
+    And the JSON response should include:
+      """
+      { "error": "validation_error" }
+      """
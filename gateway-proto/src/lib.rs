@@ -1,3 +1,5 @@
 pub mod config {
     tonic::include_proto!("gateway.config");
 }
+
+pub mod path;
@@ -0,0 +1,101 @@
+//! Path templates such as `/users/{id}`, shared so the control plane
+//! accepts exactly the templates the data plane can match.
+
+/// A path such as `/users/{id}/orders`. Each `{name}` captures exactly one
+/// non-empty segment; all other segments must match literally.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathTemplate {
+    segments: Vec<Segment>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+impl PathTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        if !template.starts_with('/') {
+            return Err(format!("path template {template} must start with '/'"));
+        }
+        let mut names = Vec::new();
+        let segments = template[1..]
+            .split('/')
+            .map(|segment| {
+                match segment
+                    .strip_prefix('{')
+                    .and_then(|rest| rest.strip_suffix('}'))
+                {
+                    Some(name) if is_param_name(name) => {
+                        if names.contains(&name) {
+                            return Err(format!("duplicate template variable {name}"));
+                        }
+                        names.push(name);
+                        Ok(Segment::Param(name.to_string()))
+                    }
+                    Some(_) => Err(format!("invalid template variable in segment {segment}")),
+                    None if segment.contains(['{', '}']) => Err(format!(
+                        "template variables must span a whole segment: {segment}"
+                    )),
+                    None => Ok(Segment::Literal(segment.to_string())),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { segments })
+    }
+
+    /// The variables captured from `path`, or `None` when it does not match.
+    pub fn captures(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let rest = path.strip_prefix('/')?;
+        let mut parts = rest.split('/');
+        let mut captured = Vec::new();
+        for segment in &self.segments {
+            let part = parts.next()?;
+            match segment {
+                Segment::Literal(literal) if literal == part => {}
+                Segment::Param(name) if !part.is_empty() => {
+                    captured.push((name.clone(), part.to_string()));
+                }
+                _ => return None,
+            }
+        }
+        parts.next().is_none().then_some(captured)
+    }
+
+    /// Names of the template's variables, in path order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Param(name) => Some(name.as_str()),
+            Segment::Literal(_) => None,
+        })
+    }
+
+    /// Length of the literal segments; longer is more specific.
+    pub fn literal_len(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => literal.len(),
+                Segment::Param(_) => 0,
+            })
+            .sum()
+    }
+}
+
+fn is_param_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_malformed_templates() {
+        assert!(PathTemplate::parse("users/{id}").is_err());
+        assert!(PathTemplate::parse("/users/{}").is_err());
+        assert!(PathTemplate::parse("/users/id-{id}").is_err());
+        assert!(PathTemplate::parse("/a/{id}/b/{id}").is_err());
+    }
+}
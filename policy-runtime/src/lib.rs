@@ -9,3 +9,9 @@ impl PolicyRuntime {
         Self
     }
 }
+
+impl Default for PolicyRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}